@@ -1,7 +1,64 @@
+use super::bus::{self, BusStyle};
 use super::designator::Designator;
+use super::group::Group;
+use super::range::{DesignatorIter, DesignatorRange};
 use super::token::{CLOSE_PAREN, COMMA, OPEN_PAREN, RANGE};
+use std::collections::BTreeMap;
+
+#[derive(Debug, Clone)]
+pub struct BuildOptions {
+    // 範囲記号 (R1~5 の ~)
+    pub range_symbol: String,
+    // 参照名の区切り (R1,2 の ,)
+    pub separator: String,
+    // 括弧なしと括弧ありのまとまりの区切り
+    pub group_separator: String,
+    // 括弧ありのまとまりを出力する順番 (含まれない種類は最後に出力する)
+    pub group_order: Vec<Group>,
+    // 省略せずに接頭辞を毎回付けるか (R1~R5)
+    pub repeat_prefix: bool,
+    // 範囲にまとめる最小の連続数
+    pub min_run_len: usize,
+    // 1行の最大文字数 (None は折り返さない)
+    pub max_width: Option<usize>,
+    // 折り返した行頭の参照名に接頭辞を付けるか
+    pub wrap_with_prefix: bool,
+    // 連続する番号をバス記法 (D[0..7]) でまとめる
+    pub bus_style: Option<BusStyle>,
+    // 接頭辞ごとのゲート (接尾辞) の数
+    // U: 4 の場合、U1A~U1D, U2A~U2D は U1A~2D にまとめる
+    pub gates: BTreeMap<String, usize>,
+    // 同じ番号の接尾辞は番号を省略する (U1A~D, U2A,C)
+    // 解析時は接尾辞の補完になるので、厳密モードではエラーになる
+    pub compress_suffixes: bool,
+    // 並べ替えずに入力の順番を保ち、連続する参照名だけを範囲にまとめる
+    pub preserve_order: bool,
+}
+
+impl Default for BuildOptions {
+    fn default() -> Self {
+        Self {
+            range_symbol: RANGE.to_string(),
+            separator: COMMA.to_string(),
+            group_separator: format!("{}\n", COMMA),
+            group_order: Group::ALL.to_vec(),
+            repeat_prefix: false,
+            min_run_len: 3,
+            max_width: None,
+            wrap_with_prefix: false,
+            bus_style: None,
+            gates: BTreeMap::new(),
+            compress_suffixes: false,
+            preserve_order: false,
+        }
+    }
+}
 
 pub fn build(designators: Vec<String>) -> String {
+    build_with_options(designators, &BuildOptions::default())
+}
+
+pub fn build_with_options(designators: Vec<String>, options: &BuildOptions) -> String {
     if designators.is_empty() {
         return String::new();
     }
@@ -9,70 +66,370 @@ pub fn build(designators: Vec<String>) -> String {
         return designators[0].clone();
     }
 
-    let designators: (Vec<_>, Vec<_>) = designators
-        .iter()
-        .map(|s| Designator::from(s.as_ref()))
-        .collect::<Vec<_>>()
+    build_designators(
+        designators
+            .iter()
+            .map(|s| Designator::from_lenient(s.as_ref()))
+            .collect(),
+        options,
+    )
+}
+
+pub fn build_designators(designators: Vec<Designator>, options: &BuildOptions) -> String {
+    build_ranges(singles(designators), options)
+}
+
+// 範囲を展開せずにまとめる
+pub fn build_ranges(ranges: Vec<DesignatorRange>, options: &BuildOptions) -> String {
+    layout(&build_items(ranges, options), options)
+}
+
+fn singles(designators: Vec<Designator>) -> Vec<DesignatorRange> {
+    designators
         .into_iter()
-        .partition(|des| des.has_paren());
+        .map(DesignatorRange::single)
+        .collect()
+}
 
-    let mut designator = String::new();
-    if !designators.1.is_empty() {
-        designator.extend(build_inner(designators.1, false).chars());
+pub fn build_inner(designators: Vec<Designator>, group: Option<Group>) -> String {
+    let options = BuildOptions::default();
+    let items = build_group(singles(designators), group, &options)
+        .into_iter()
+        .map(|item| (options.separator.as_str(), item))
+        .collect::<Vec<_>>();
+    layout(&items, &options)
+}
+
+// 各要素が max_len 文字以内で、単独で解析できる文字列に分割する
+// 分割できない範囲などが max_len を超える場合は、その要素だけの文字列になる
+pub fn build_chunks(
+    designators: Vec<String>,
+    max_len: usize,
+    options: &BuildOptions,
+) -> Vec<String> {
+    let designators = designators
+        .iter()
+        .map(|s| Designator::from_lenient(s.as_ref()))
+        .collect();
+    let items = build_items(singles(designators), options);
+
+    // 分割した先頭では括弧を開き直す
+    let start_text = |i: usize| {
+        let item = &items[i].1;
+        match item.group {
+            Some(group) if i > 0 && items[i - 1].1.group == item.group => {
+                format!("{}{}", group.open(), item.full_text)
+            }
+            _ => item.full_text.clone(),
+        }
+    };
+    // 分割した末尾では括弧を閉じる
+    let end_text = |i: usize| {
+        let item = &items[i].1;
+        match item.group {
+            Some(group)
+                if items
+                    .get(i + 1)
+                    .is_some_and(|(_, next)| next.group == item.group) =>
+            {
+                group.close().to_string()
+            }
+            _ => String::new(),
+        }
+    };
+
+    // チャンクの中はグループの区切りではなく、通常の区切りでつなぐ
+    let sep = options.separator.as_str();
+    let mut chunks: Vec<String> = Vec::new();
+    let mut chunk = String::new();
+    for (i, (_, item)) in items.iter().enumerate() {
+        if !chunk.is_empty() {
+            let len = chunk.chars().count()
+                + sep.chars().count()
+                + item.text.chars().count()
+                + end_text(i).chars().count();
+            if len <= max_len {
+                chunk += sep;
+                chunk += item.text.as_str();
+                continue;
+            }
+            chunk += end_text(i - 1).as_str();
+            chunks.push(chunk);
+        }
+        chunk = start_text(i);
     }
+    if !chunk.is_empty() {
+        chunks.push(chunk);
+    }
+
+    chunks
+}
 
-    if !designators.0.is_empty() {
-        if !designator.is_empty() {
-            designator += ",\n";
+// 区切りで分割できない単位 (参照名1つ、もしくは範囲1つ)
+#[derive(Debug, Clone)]
+struct Item {
+    // 前の参照名との共通部分を省略した表記
+    text: String,
+    // 省略しない表記 (行頭用)
+    full_text: String,
+    // 括弧ありのまとまりに含まれる場合はその種類
+    group: Option<Group>,
+}
+
+// 直前の区切りと組にして返す
+fn build_items(ranges: Vec<DesignatorRange>, options: &BuildOptions) -> Vec<(&str, Item)> {
+    // 括弧なし、括弧ありの順に、括弧の種類ごとに分ける
+    // 順番を保つ場合は、括弧の種類が同じものが続く間だけをまとめる
+    let mut groups: Vec<(Option<Group>, Vec<DesignatorRange>)> = Vec::new();
+    for range in ranges {
+        let group = range.start().group();
+        let same_group = if options.preserve_order {
+            groups.last_mut().filter(|(g, _)| *g == group)
+        } else {
+            groups.iter_mut().find(|(g, _)| *g == group)
+        };
+        match same_group {
+            Some((_, ranges)) => ranges.push(range),
+            None => groups.push((group, vec![range])),
+        }
+    }
+    if !options.preserve_order {
+        groups.sort_by_key(|(group, _)| {
+            group.map(|group| {
+                options
+                    .group_order
+                    .iter()
+                    .position(|g| *g == group)
+                    .unwrap_or(options.group_order.len() + group as usize)
+            })
+        });
+    }
+
+    let mut items: Vec<(&str, Item)> = Vec::new();
+    for (group, ranges) in groups {
+        let mut iter = build_group(ranges, group, options).into_iter();
+        if let Some(first) = iter.next() {
+            // 括弧ありのまとまりの前は改行して区切る
+            let sep = if group.is_some() {
+                options.group_separator.as_str()
+            } else {
+                options.separator.as_str()
+            };
+            items.push((sep, first));
+        }
+        items.extend(iter.map(|item| (options.separator.as_str(), item)));
+    }
+
+    items
+}
+
+fn layout(items: &[(&str, Item)], options: &BuildOptions) -> String {
+    let mut designator = String::new();
+    // 現在の行の文字数
+    let mut width = 0;
+
+    for (i, (sep, item)) in items.iter().enumerate() {
+        if i == 0 {
+            designator += item.text.as_str();
+            width = item.text.chars().count();
+            continue;
+        }
+
+        // 区切りに改行を含む場合は、そこで改行される
+        if let Some((_, rest)) = sep.rsplit_once('\n') {
+            designator += sep;
+            designator += item.text.as_str();
+            width = rest.chars().count() + item.text.chars().count();
+            continue;
+        }
+
+        // 行が続く場合に末尾に残る区切り
+        let trailing = items.get(i + 1).map_or(0, |(sep, _)| {
+            let sep = sep.split('\n').next().unwrap_or_default();
+            sep.trim_end().chars().count()
+        });
+        let is_overflowed = options.max_width.is_some_and(|max| {
+            width + sep.chars().count() + item.text.chars().count() + trailing > max
+        });
+
+        if is_overflowed {
+            // 区切りの後で改行する
+            let text = if options.wrap_with_prefix {
+                item.full_text.as_str()
+            } else {
+                item.text.as_str()
+            };
+            designator += sep.trim_end();
+            designator.push('\n');
+            designator += text;
+            width = text.chars().count();
+        } else {
+            designator += sep;
+            designator += item.text.as_str();
+            width += sep.chars().count() + item.text.chars().count();
         }
-        designator.extend(build_inner(designators.0, true).chars())
     }
 
     designator
 }
 
-pub fn build_inner(designators: Vec<Designator>, has_paren: bool) -> String {
-    let mut designators = if has_paren {
+fn build_group(
+    ranges: Vec<DesignatorRange>,
+    group: Option<Group>,
+    options: &BuildOptions,
+) -> Vec<Item> {
+    let mut ranges = if group.is_some() {
         // すべて括弧を外す
-        designators
+        ranges
             .into_iter()
-            .map(|des| des.without_parentheses())
+            .map(|range| range.without_group())
             .collect::<Vec<_>>()
     } else {
-        designators
+        ranges
     };
     // ソート
-    designators.sort();
+    if !options.preserve_order {
+        ranges.sort_by(|a, b| a.start().cmp(b.start()));
+    }
 
-    let mut differences: Vec<(String, isize)> = vec![(designators[0].to_string(), -1)];
+    // 属性は範囲などのまとまりの後にまとめて付けるので、表記からは除く
+    let bare = |des: &Designator| des.with_attribute(None);
+    // 直前の参照名 b に対して省略した表記
+    let text_of = |a: &Designator, b: Option<&Designator>| match b {
+        Some(b) if !options.repeat_prefix => omitted_suffix(a, b, false, options)
+            .map(String::from)
+            .unwrap_or_else(|| bare(a).to_omitted_string(&bare(b))),
+        _ => bare(a).to_string(),
+    };
+    // a が b の次の参照名か
+    // ゲートとして数えるのは、番号が異なり両方に接尾辞がある場合だけ
+    let is_next = |a: &Designator, b: &Designator| {
+        let difference = match options.gates.get(a.prefix()) {
+            Some(gates)
+                if a.number() != b.number() && a.suffix().is_some() && b.suffix().is_some() =>
+            {
+                a.gate_difference(b, *gates)
+            }
+            _ => a.difference(b),
+        };
+        difference == Some(1)
+    };
+    // 2つ未満の連続は範囲にできない
+    let min_run_len = options.min_run_len.max(2);
 
-    differences.extend(
-        designators
+    let mut items: Vec<Item> = Vec::new();
+    // 直前のまとまりの最後の参照名
+    let mut prev: Option<Designator> = None;
+    for chunk in ranges.chunk_by(|b, a| is_next(a.start(), b.end())) {
+        let (first_designator, last_designator) = (chunk[0].start(), chunk[chunk.len() - 1].end());
+        // 範囲は展開せずに数える
+        let count = chunk
             .iter()
-            .skip(1)
-            .zip(designators.iter())
-            .map(|(a, b)| (a.to_omitted_string(b), a.difference(b).unwrap_or(-1))),
-    );
-
-    let chunks = differences.chunk_by(|_, (_, b)| *b == 1);
-    let mut designator = chunks.fold(String::new(), |mut acc, chunk| {
-        let (mut v, _): (Vec<_>, Vec<_>) = chunk.iter().cloned().unzip();
-        if !acc.is_empty() {
-            acc.push(COMMA);
+            .fold(0usize, |acc, range| acc.saturating_add(range.count()));
+        let len = items.len();
+
+        let bus_style = options
+            .bus_style
+            .filter(|_| first_designator.suffix().is_none());
+        if let (Some(style), true) = (bus_style, count >= min_run_len) {
+            // バス記法の名前は接尾辞を除いた番号より前の部分
+            let mut name: String = first_designator
+                .path()
+                .iter()
+                .map(|segment| segment.to_string())
+                .collect();
+            name.extend(first_designator.sign());
+            name += first_designator.prefix();
+            let text = bus::format(
+                &name,
+                first_designator.number(),
+                last_designator.number(),
+                first_designator.width(),
+                style,
+            );
+            items.push(Item {
+                text: text.clone(),
+                full_text: text,
+                group,
+            });
+        } else if count >= min_run_len {
+            // 範囲は最初と最後だけ残す
+            let first_text = text_of(first_designator, prev.as_ref());
+            // 範囲の終わりは、範囲の始まりと同じ番号なら接尾辞だけにできる
+            // 番号が異なる場合は、直前の参照名に対して省略された接尾辞を番号付きに戻す
+            let last_text = match omitted_suffix(last_designator, first_designator, true, options) {
+                Some(suffix) => suffix.to_string(),
+                None if options.repeat_prefix => bare(last_designator).to_string(),
+                None => bare(last_designator).to_omitted_string(&bare(first_designator)),
+            };
+            items.push(Item {
+                text: format!("{}{}{}", first_text, options.range_symbol, last_text),
+                full_text: format!(
+                    "{}{}{}",
+                    bare(first_designator),
+                    options.range_symbol,
+                    last_text
+                ),
+                group,
+            });
+        } else {
+            // 範囲にならない短い連続だけを展開する
+            for designator in DesignatorIter::new(chunk.to_vec()) {
+                items.push(Item {
+                    text: text_of(&designator, prev.as_ref()),
+                    full_text: bare(&designator).to_string(),
+                    group,
+                });
+                prev = Some(designator);
+            }
+        }
+
+        // 属性が同じ参照名だけが連続するので、まとまりごとに付ける
+        if let Some(attribute) = first_designator.attribute() {
+            let attribute = format!("{}{}{}", OPEN_PAREN, attribute, CLOSE_PAREN);
+            for item in &mut items[len..] {
+                item.text += attribute.as_str();
+                item.full_text += attribute.as_str();
+            }
+        }
+
+        prev = Some(last_designator.clone());
+    }
+
+    if let Some(group) = group {
+        if let Some(first) = items.first_mut() {
+            first.text.insert(0, group.open());
+            first.full_text.insert(0, group.open());
         }
-        let mut sep = COMMA.to_string();
-        if chunk.len() > 2 {
-            v.drain(1..(v.len() - 1));
-            sep = RANGE.to_string();
+        if let Some(last) = items.last_mut() {
+            last.text.push(group.close());
+            last.full_text.push(group.close());
         }
+    }
 
-        acc + v.join(sep.as_str()).as_str()
-    });
+    items
+}
 
-    if has_paren {
-        designator.insert(0, OPEN_PAREN);
-        designator.push(CLOSE_PAREN);
+// a の番号を省略して接尾辞だけにできる場合は、その接尾辞を返す
+// 区切りの後は1文字の接尾辞しか補完できないが、範囲の終わりは複数文字でも補完できる
+fn omitted_suffix<'a>(
+    a: &'a Designator,
+    b: &Designator,
+    is_range_end: bool,
+    options: &BuildOptions,
+) -> Option<&'a str> {
+    if !options.compress_suffixes || options.repeat_prefix {
+        return None;
     }
+    let (suffix, other) = (a.suffix()?, b.suffix()?);
+    let is_same_number = a.path() == b.path()
+        && a.sign() == b.sign()
+        && a.prefix() == b.prefix()
+        && a.number() == b.number()
+        && a.width() == b.width();
+    let is_same_case = suffix.chars().all(|c| c.is_ascii_lowercase())
+        == other.chars().all(|c| c.is_ascii_lowercase());
 
-    designator
+    (is_same_number && is_same_case && (is_range_end || suffix.chars().count() == 1))
+        .then_some(suffix)
 }
@@ -0,0 +1,65 @@
+// バス記法 (D[0..7], ADDR[15:0])
+
+const OPEN_BRACKET: char = '[';
+const CLOSE_BRACKET: char = ']';
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BusStyle {
+    // D[0..7]
+    KiCad,
+    // D[7..0] (上位ビットから)
+    Altium,
+    // D[7:0]
+    Verilog,
+}
+
+// バス記法を名前、始まり、終わり、番号の桁数に分ける
+pub(crate) fn split(s: &str) -> Option<(&str, usize, usize, usize)> {
+    let (name, rest) = s.split_once(OPEN_BRACKET)?;
+    let inner = rest.strip_suffix(CLOSE_BRACKET)?;
+    let (first, last) = inner.split_once("..").or_else(|| inner.split_once(':'))?;
+    if name.is_empty() || first.is_empty() || last.is_empty() {
+        return None;
+    }
+    if !first
+        .chars()
+        .chain(last.chars())
+        .all(|c| c.is_ascii_digit())
+    {
+        return None;
+    }
+
+    // ゼロ埋めされている場合だけ桁数を保持する
+    let width = if first.starts_with('0') && first.len() > 1 {
+        first.len()
+    } else {
+        0
+    };
+
+    Some((name, first.parse().ok()?, last.parse().ok()?, width))
+}
+
+pub(crate) fn format(
+    name: &str,
+    first: usize,
+    last: usize,
+    width: usize,
+    style: BusStyle,
+) -> String {
+    let (first, last, sep) = match style {
+        BusStyle::KiCad => (first, last, ".."),
+        BusStyle::Altium => (last, first, ".."),
+        BusStyle::Verilog => (last, first, ":"),
+    };
+
+    format!(
+        "{}{}{:0width$}{}{:0width$}{}",
+        name,
+        OPEN_BRACKET,
+        first,
+        sep,
+        last,
+        CLOSE_BRACKET,
+        width = width
+    )
+}
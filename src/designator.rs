@@ -1,94 +1,172 @@
+use super::group::Group;
 use super::token::{CLOSE_PAREN, OPEN_PAREN};
 use core::cmp;
 use std::fmt;
 use std::fmt::Write;
 use std::str::FromStr;
 
+// 階層の区切り記号
+// '.' は A1.R5 のような区切り、'=', '+', '-' は IEC 81346 の視点記号 (=A1-R12)
+const HIERARCHY_SIGNS: [char; 4] = ['.', '=', '+', '-'];
+// 視点記号のうち、範囲記号と重ならないもの
+// これらで始まる参照名では、'-' を範囲記号ではなく区切り記号とする (=A1-R1~5)
+pub(crate) const ASPECT_SIGNS: [char; 2] = ['=', '+'];
+pub(crate) const PRODUCT_SIGN: char = '-';
+
+// 階層参照名の上位の階層 (ユニット、アセンブリ)
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Segment {
+    sign: Option<char>,
+    designator: Designator,
+}
+
+impl fmt::Display for Segment {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if let Some(sign) = self.sign {
+            f.write_char(sign)?
+        }
+        write!(f, "{}", self.designator)
+    }
+}
+
+impl Segment {
+    pub fn sign(&self) -> Option<char> {
+        self.sign
+    }
+
+    pub fn designator(&self) -> &Designator {
+        &self.designator
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct Designator {
+    // 上位の階層 (1A2R3 の 1, A2)
+    path: Vec<Segment>,
+    // 最下位の階層の区切り記号 (A1.R5 の .)
+    sign: Option<char>,
     prefix: String,
     number: usize,
-    suffix: Option<char>,
-    has_paren: bool,
+    // 番号の桁数 (R001 は 3)、番号がない場合は 0
+    width: usize,
+    suffix: Option<String>,
+    // 括弧で囲まれている場合はその種類
+    group: Option<Group>,
+    // 参照名に続く括弧内の属性 (R1(DNP) の DNP)
+    attribute: Option<String>,
+}
+
+// 接尾辞を A=1, Z=26, AA=27 のような連番に変換する (表計算の列名と同じ)
+// 大きすぎる場合は None
+fn suffix_to_index(suffix: &str) -> Option<usize> {
+    suffix.chars().try_fold(0usize, |acc, c| {
+        let n = (c.to_ascii_lowercase() as usize) - ('a' as usize) + 1;
+        acc.checked_mul(26)?.checked_add(n)
+    })
+}
+
+fn index_to_suffix(mut index: usize, is_lowercase: bool) -> String {
+    let base = if is_lowercase { b'a' } else { b'A' };
+    let mut suffix: Vec<char> = Vec::new();
+    while index > 0 {
+        index -= 1;
+        suffix.push(char::from(base + (index % 26) as u8));
+        index /= 26;
+    }
+    suffix.iter().rev().collect()
+}
+
+// 番号の桁数 (0 は 1 桁)
+fn digit_count(number: usize) -> usize {
+    number.checked_ilog10().map_or(1, |log| log as usize + 1)
+}
+
+// a - b を isize で返す、isize に収まらない場合は None
+fn signed_difference(a: usize, b: usize) -> Option<isize> {
+    isize::try_from(a as i128 - b as i128).ok()
+}
+
+// 接尾辞はすべて小文字、もしくはすべて大文字のアルファベット
+fn is_suffix(s: &str) -> bool {
+    !s.is_empty()
+        && (s.chars().all(|c| c.is_ascii_lowercase()) || s.chars().all(|c| c.is_ascii_uppercase()))
+        && suffix_to_index(s).is_some()
 }
 
 impl fmt::Display for Designator {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        if self.has_paren {
-            f.write_char(OPEN_PAREN)?
+        if let Some(group) = self.group {
+            f.write_char(group.open())?
+        }
+
+        for segment in &self.path {
+            write!(f, "{}", segment)?;
+        }
+
+        if let Some(sign) = self.sign {
+            f.write_char(sign)?
         }
 
         f.write_str(&self.prefix)?;
 
-        if self.number > 0 {
-            write!(f, "{}", self.number)?;
+        if self.has_number() {
+            write!(f, "{:0width$}", self.number, width = self.width)?;
         }
 
-        if let Some(suffix) = self.suffix {
-            f.write_char(suffix)?
+        if let Some(suffix) = &self.suffix {
+            f.write_str(suffix)?
         }
 
-        if self.has_paren {
-            f.write_char(CLOSE_PAREN)?
+        if let Some(attribute) = &self.attribute {
+            write!(f, "{}{}{}", OPEN_PAREN, attribute, CLOSE_PAREN)?
+        }
+
+        if let Some(group) = self.group {
+            f.write_char(group.close())?
         }
 
         Ok(())
     }
 }
-impl From<&str> for Designator {
-    fn from(s: &str) -> Self {
-        let has_paren = s.starts_with(OPEN_PAREN) && s.ends_with(CLOSE_PAREN);
-
-        let chars: Vec<char> = if has_paren {
-            s.chars().skip(1).take(s.len() - 2).collect()
-        } else {
-            s.chars().collect()
-        };
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ParseDesignatorError {
+    // 括弧を除いた参照名が空
+    Empty,
+    // 番号が usize に収まらない
+    NumberOverflow(String),
+}
 
-        // 空の参照名
-        if chars.is_empty() {
-            let mut designator = Designator::new();
-            designator.has_paren = has_paren;
-            return designator;
+impl fmt::Display for ParseDesignatorError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ParseDesignatorError::Empty => f.write_str("designator is empty"),
+            ParseDesignatorError::NumberOverflow(number) => {
+                write!(f, "designator number is too large: {}", number)
+            }
         }
+    }
+}
 
-        // 数字があるか
-        let Some(num_start) = chars.iter().position(|c| c.is_ascii_digit()) else {
-            // なければ単語の参照名
-            return Designator::word(String::from_iter(chars), has_paren);
-        };
+impl std::error::Error for ParseDesignatorError {}
 
-        // 数字の終わり
-        let num_end = num_start
-            + chars
-                .iter()
-                .skip(num_start)
-                .take_while(|c| c.is_ascii_digit())
-                .count()
-            - 1;
+impl FromStr for Designator {
+    type Err = ParseDesignatorError;
 
-        // 接尾辞があるか
-        // 接尾辞は1文字のアルファベット記号のみとし、それ以外は単語参照名
-        // 接尾辞が複数文字の場合やアルファベットでない場合、計算が非常に煩雑になるため限定しておく
-        let suffix_start = num_end + 1;
-        // 接尾辞は複数文字か?
-        if (suffix_start + 1) < chars.len() {
-            return Designator::word(String::from_iter(chars), has_paren);
-        }
-        let suffix = chars.get(suffix_start).and_then(|c| Some(*c));
-        // 接尾辞はアルファベットか？
-        if suffix.is_some_and(|c| !c.is_ascii_alphabetic()) {
-            return Designator::word(String::from_iter(chars), has_paren);
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let designator = Designator::parse(s)?;
+        if designator.is_empty() {
+            return Err(ParseDesignatorError::Empty);
         }
 
-        let number = String::from_iter(chars[num_start..=num_end].iter());
+        Ok(designator)
+    }
+}
 
-        Self {
-            prefix: String::from_iter(chars.iter().take(num_start)),
-            number: usize::from_str(&number).unwrap(),
-            suffix,
-            has_paren,
-        }
+impl TryFrom<&str> for Designator {
+    type Error = ParseDesignatorError;
+
+    fn try_from(s: &str) -> Result<Self, Self::Error> {
+        Designator::from_str(s)
     }
 }
 
@@ -108,39 +186,246 @@ impl PartialOrd for Designator {
 
 impl Ord for Designator {
     fn cmp(&self, other: &Self) -> cmp::Ordering {
-        self.has_paren
-            .cmp(&other.has_paren)
+        self.group
+            .cmp(&other.group)
+            .then(self.attribute.cmp(&other.attribute))
+            .then(self.path.cmp(&other.path))
+            .then(self.sign.cmp(&other.sign))
             .then(self.prefix.cmp(&other.prefix))
+            .then(self.has_number().cmp(&other.has_number()))
+            // 表記上の桁数 (ゼロ埋めを含む) ごとにまとめる
+            // R1~3 と R001~003 は別々にまとまり、R099 の次は R100 となる
+            .then(self.width.cmp(&other.width))
             .then(self.number.cmp(&other.number))
+            .then(self.suffix_index().cmp(&other.suffix_index()))
             .then(self.suffix.cmp(&other.suffix))
     }
 }
 
+// 全体を囲む括弧を外し、括弧の種類と組にして返す
+// 位置はバイト単位なので、文字数ではなく括弧の文字で切り出す
+pub(crate) fn strip_group(s: &str) -> (&str, Option<Group>) {
+    Group::ALL
+        .into_iter()
+        .find_map(|group| {
+            let inner = s.strip_prefix(group.open())?.strip_suffix(group.close())?;
+            Some((inner, Some(group)))
+        })
+        .unwrap_or((s, None))
+}
+
+// 末尾の括弧内の属性を分け、属性より前の部分と組にして返す (C3(10uF) -> C3, 10uF)
+// 括弧が先頭から始まる場合や、中身が空の場合は属性としない
+fn split_attribute(s: &str) -> (&str, Option<&str>) {
+    let Some(rest) = s.strip_suffix(CLOSE_PAREN) else {
+        return (s, None);
+    };
+    // 対応する開き括弧を後ろから探す
+    let mut depth = 0;
+    for (i, c) in rest.char_indices().rev() {
+        match c {
+            CLOSE_PAREN => depth += 1,
+            OPEN_PAREN if depth > 0 => depth -= 1,
+            OPEN_PAREN if i > 0 && i + 1 < rest.len() => {
+                return (&s[..i], Some(&rest[(i + 1)..]));
+            }
+            OPEN_PAREN => break,
+            _ => (),
+        }
+    }
+    (s, None)
+}
+
 impl Designator {
+    // パーサーからの変換は失敗させない
+    // 番号が大きすぎる場合は単語の参照名として扱う
+    pub(crate) fn from_lenient(s: &str) -> Self {
+        Designator::parse(s).unwrap_or_else(|_| {
+            let (word, group) = strip_group(s);
+            Designator::word(word.to_string(), group)
+        })
+    }
+
+    fn parse(s: &str) -> Result<Self, ParseDesignatorError> {
+        let (inner, group) = strip_group(s);
+        let (inner, attribute) = split_attribute(inner);
+        let chars: Vec<char> = inner.chars().collect();
+
+        let mut designator = Designator::parse_flat(&chars, group)?;
+        // 単語になる場合は、階層参照名かどうか確認する
+        if designator.is_word() {
+            if let Some(hierarchical) = Designator::parse_hierarchical(&chars, group) {
+                designator = hierarchical;
+            }
+        }
+
+        Ok(Self {
+            attribute: attribute.map(String::from),
+            ..designator
+        })
+    }
+
+    // 1A2R3, A1.R5, =A1-R12 のような階層参照名
+    // 各階層は番号を持ち、最下位の階層は接頭辞も持つ必要がある
+    fn parse_hierarchical(chars: &[char], group: Option<Group>) -> Option<Self> {
+        let mut segments: Vec<(Option<char>, String)> = vec![(None, String::new())];
+        for c in chars {
+            if HIERARCHY_SIGNS.contains(c) {
+                segments.push((Some(*c), String::new()));
+            } else {
+                segments.last_mut().unwrap().1.push(*c);
+            }
+        }
+        // 先頭が区切り記号の場合
+        if segments[0].1.is_empty() {
+            segments.remove(0);
+        }
+
+        // 区切り記号がない場合は、接頭辞と番号の組ごとに分ける (1A2R3 -> 1, A2, R3)
+        if segments.len() == 1 && segments[0].0.is_none() {
+            // 英数字以外を含む場合 (A1:C4 など) は階層参照名としない
+            if !chars.iter().all(|c| c.is_ascii_alphanumeric()) {
+                return None;
+            }
+            let mut groups: Vec<String> = vec![String::new()];
+            let mut prev: Option<char> = None;
+            for c in chars {
+                if !c.is_ascii_digit() && prev.is_some_and(|p| p.is_ascii_digit()) {
+                    groups.push(String::new());
+                }
+                groups.last_mut().unwrap().push(*c);
+                prev = Some(*c);
+            }
+            // 末尾のアルファベットだけのまとまりは接尾辞
+            if groups.len() > 1 && !groups.last().unwrap().chars().any(|c| c.is_ascii_digit()) {
+                let suffix = groups.pop().unwrap();
+                groups.last_mut().unwrap().push_str(&suffix);
+            }
+            segments = groups.into_iter().map(|group| (None, group)).collect();
+        }
+
+        if segments.len() < 2 {
+            return None;
+        }
+
+        let mut path: Vec<Segment> = Vec::new();
+        for (sign, s) in segments {
+            let chars: Vec<char> = s.chars().collect();
+            let designator = Designator::parse_flat(&chars, None).ok()?;
+            if !designator.has_number() {
+                return None;
+            }
+            path.push(Segment { sign, designator });
+        }
+
+        let leaf = path.pop().unwrap();
+        if leaf.designator.prefix.is_empty() {
+            return None;
+        }
+
+        Some(Self {
+            path,
+            sign: leaf.sign,
+            group,
+            ..leaf.designator
+        })
+    }
+
+    fn parse_flat(chars: &[char], group: Option<Group>) -> Result<Self, ParseDesignatorError> {
+        // 空の参照名
+        if chars.is_empty() {
+            let mut designator = Designator::new();
+            designator.group = group;
+            return Ok(designator);
+        }
+
+        // 数字があるか
+        let Some(num_start) = chars.iter().position(|c| c.is_ascii_digit()) else {
+            // なければ単語の参照名
+            return Ok(Designator::word(String::from_iter(chars), group));
+        };
+
+        // 数字の終わり
+        let num_end = num_start
+            + chars
+                .iter()
+                .skip(num_start)
+                .take_while(|c| c.is_ascii_digit())
+                .count()
+            - 1;
+
+        // 接尾辞があるか
+        // 接尾辞は大文字もしくは小文字のみのアルファベットとし、それ以外は単語参照名
+        // A~Z の次は AA, AB のように表計算の列名と同じ連番として扱う
+        let suffix_start = num_end + 1;
+        let suffix = String::from_iter(chars.iter().skip(suffix_start));
+        let suffix = if suffix.is_empty() {
+            None
+        } else if is_suffix(&suffix) {
+            Some(suffix)
+        } else {
+            return Ok(Designator::word(String::from_iter(chars), group));
+        };
+
+        let number = String::from_iter(chars[num_start..=num_end].iter());
+
+        Ok(Self {
+            path: Vec::new(),
+            sign: None,
+            prefix: String::from_iter(chars.iter().take(num_start)),
+            width: number.len(),
+            number: usize::from_str(&number)
+                .map_err(|_| ParseDesignatorError::NumberOverflow(number))?,
+            suffix,
+            group,
+            attribute: None,
+        })
+    }
+
     fn new() -> Self {
         Self {
+            path: Vec::new(),
+            sign: None,
             prefix: String::new(),
             number: 0,
+            width: 0,
             suffix: None,
-            has_paren: false,
+            group: None,
+            attribute: None,
         }
     }
 
-    fn word(s: String, has_paren: bool) -> Self {
+    fn word(s: String, group: Option<Group>) -> Self {
         Self {
+            path: Vec::new(),
+            sign: None,
             prefix: s,
             number: 0,
+            width: 0,
             suffix: None,
-            has_paren,
+            group,
+            attribute: None,
         }
     }
 
     pub fn is_empty(&self) -> bool {
-        self.prefix.is_empty() && self.number == 0 && self.suffix.is_none()
+        self.prefix.is_empty() && !self.has_number() && self.suffix.is_none()
     }
 
     pub fn is_word(&self) -> bool {
-        !self.prefix.is_empty() && self.number == 0 && self.suffix.is_none()
+        !self.prefix.is_empty() && !self.has_number() && self.suffix.is_none()
+    }
+
+    pub fn path(&self) -> &[Segment] {
+        self.path.as_slice()
+    }
+
+    pub fn sign(&self) -> Option<char> {
+        self.sign
+    }
+
+    pub fn is_hierarchical(&self) -> bool {
+        !self.path.is_empty()
     }
 
     pub fn prefix(&self) -> &str {
@@ -151,20 +436,105 @@ impl Designator {
         self.number
     }
 
-    pub fn suffix(&self) -> Option<char> {
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    fn has_number(&self) -> bool {
+        self.width > 0
+    }
+
+    // ゼロ埋めされている場合の桁数、ゼロ埋めされていなければ 0
+    // R099 の次の R100 と、入力された R100 を同じものとして扱うため
+    pub(crate) fn padded_width(&self) -> usize {
+        if self.width > digit_count(self.number) {
+            self.width
+        } else {
+            0
+        }
+    }
+
+    // 番号を変えたときの桁数
+    // ゼロ埋めされていればその桁数のまま、されていなければ新しい番号の桁数 (R10 の前は R9)
+    fn width_for(&self, number: usize) -> usize {
+        match self.padded_width() {
+            0 => digit_count(number),
+            width => width.max(digit_count(number)),
+        }
+    }
+
+    // ゼロ埋めされたまま表せる最後の番号の参照名 (R001 なら R099)
+    // ゼロ埋めされていない場合や、すべての番号をゼロ埋めできる場合は None
+    pub(crate) fn last_padded(&self) -> Option<Self> {
+        let width = self.padded_width();
+        if width == 0 || self.suffix.is_some() {
+            return None;
+        }
+        let number = 10usize.checked_pow(u32::try_from(width - 1).ok()?)? - 1;
+        Some(Self {
+            number,
+            width,
+            ..self.clone()
+        })
+    }
+
+    // ゼロ埋めの桁数を other にそろえる (R100~R099 を降順に展開するときの R99 -> R099)
+    pub(crate) fn with_padding_of(&self, other: &Designator) -> Self {
+        Self {
+            width: self.width.max(other.padded_width()),
+            ..self.clone()
+        }
+    }
+
+    // ゼロ埋めの桁数をそろえられるか
+    // ゼロ埋めされていない番号は、その桁数以下のゼロ埋めとも連続する (R099 の次は R100)
+    fn has_same_padding(&self, other: &Designator) -> bool {
+        match (self.padded_width(), other.padded_width()) {
+            (0, 0) => true,
+            (0, width) => self.width >= width,
+            (width, 0) => other.width >= width,
+            (a, b) => a == b,
+        }
+    }
+
+    pub fn suffix(&self) -> Option<&str> {
+        self.suffix.as_deref()
+    }
+
+    pub(crate) fn suffix_index(&self) -> Option<usize> {
+        self.suffix.as_deref().and_then(suffix_to_index)
+    }
+
+    fn is_lowercase_suffix(&self) -> bool {
         self.suffix
+            .as_deref()
+            .is_some_and(|s| s.chars().all(|c| c.is_ascii_lowercase()))
     }
 
+    // 丸括弧 (実装しない部品) で囲まれているか
     pub fn has_paren(&self) -> bool {
-        self.has_paren
+        self.group == Some(Group::Paren)
     }
 
-    pub fn without_parentheses(&self) -> Self {
+    pub fn group(&self) -> Option<Group> {
+        self.group
+    }
+
+    pub fn attribute(&self) -> Option<&str> {
+        self.attribute.as_deref()
+    }
+
+    pub fn with_attribute(&self, attribute: Option<&str>) -> Self {
+        Self {
+            attribute: attribute.map(String::from),
+            ..self.clone()
+        }
+    }
+
+    pub fn without_group(&self) -> Self {
         Self {
-            prefix: self.prefix.clone(),
-            number: self.number,
-            suffix: self.suffix,
-            has_paren: false,
+            group: None,
+            ..self.clone()
         }
     }
 
@@ -175,16 +545,27 @@ impl Designator {
 
         let mut omitted = String::new();
 
-        if self.prefix != other.prefix {
+        // 階層が異なる場合は、接頭辞を省略すると階層がわからなくなる
+        if self.path != other.path || self.sign != other.sign || self.prefix != other.prefix {
+            for segment in &self.path {
+                omitted += segment.to_string().as_str();
+            }
+            if let Some(sign) = self.sign {
+                omitted.push(sign);
+            }
             omitted += self.prefix.as_str()
         }
 
-        if self.number > 0 {
-            omitted += self.number.to_string().as_str();
+        if self.has_number() {
+            omitted += format!("{:0width$}", self.number, width = self.width).as_str();
         }
 
-        if let Some(suffix) = self.suffix {
-            omitted.push(suffix);
+        if let Some(suffix) = &self.suffix {
+            omitted += suffix.as_str();
+        }
+
+        if let Some(attribute) = &self.attribute {
+            omitted += format!("{}{}{}", OPEN_PAREN, attribute, CLOSE_PAREN).as_str();
         }
 
         omitted
@@ -195,31 +576,100 @@ impl Designator {
             return None;
         }
 
-        if self.suffix.is_some() {
-            let suffix = char::from(self.suffix.unwrap() as u8 + 1);
+        if let Some(index) = self.suffix_index() {
+            let suffix = index_to_suffix(index + 1, self.is_lowercase_suffix());
+            Some(Self {
+                suffix: Some(suffix),
+                ..self.clone()
+            })
+        } else {
+            let number = self.number.checked_add(1)?;
+            Some(Self {
+                number,
+                width: self.width_for(number),
+                ..self.clone()
+            })
+        }
+    }
+
+    pub fn prev(&self) -> Option<Self> {
+        if self.is_empty() || self.is_word() {
+            return None;
+        }
+
+        if let Some(index) = self.suffix_index() {
+            // 接尾辞は a (A) より前には戻せない
+            if index <= 1 {
+                return None;
+            }
+            let suffix = index_to_suffix(index - 1, self.is_lowercase_suffix());
             Some(Self {
-                prefix: self.prefix.clone(),
-                number: self.number,
                 suffix: Some(suffix),
-                has_paren: self.has_paren,
+                ..self.clone()
             })
         } else {
-            let number = self.number + 1;
+            // 番号は 0 より前には戻せない
+            if self.number == 0 {
+                return None;
+            }
+            let number = self.number - 1;
             Some(Self {
-                prefix: self.prefix.clone(),
                 number,
-                suffix: None,
-                has_paren: self.has_paren,
+                width: self.width_for(number),
+                ..self.clone()
             })
         }
     }
 
-    pub fn complement(&mut self, other: &Designator) {
+    // number 番の index 番目のゲート (U1A の A が 1) に置き換える
+    pub(crate) fn with_gate(&self, number: usize, index: usize) -> Self {
+        Self {
+            number,
+            width: self.width_for(number),
+            suffix: Some(index_to_suffix(index, self.is_lowercase_suffix())),
+            ..self.clone()
+        }
+    }
+
+    // ゲート数 gates ごとに番号が繰り上がるものとした次のゲート (U1D の次は U2A)
+    pub(crate) fn next_gate(&self, gates: usize) -> Option<Self> {
+        let index = self.suffix_index()?;
+        if index < gates {
+            Some(self.with_gate(self.number, index + 1))
+        } else {
+            Some(self.with_gate(self.number.checked_add(1)?, 1))
+        }
+    }
+
+    // ゲート数 gates ごとに番号が繰り上がるものとした前のゲート (U2A の前は U1D)
+    pub(crate) fn prev_gate(&self, gates: usize) -> Option<Self> {
+        let index = self.suffix_index()?;
+        if index > 1 {
+            Some(self.with_gate(self.number, index - 1))
+        } else {
+            Some(self.with_gate(self.number.checked_sub(1)?, gates))
+        }
+    }
+
+    // 接頭辞を接尾辞として補完した場合は true を返す
+    pub fn complement(&mut self, other: &Designator) -> bool {
+        self.complement_inner(other, false)
+    }
+
+    // 範囲の終わりの補完
+    // U1Y~AB のように、複数文字の接頭辞も接尾辞として扱う
+    pub(crate) fn complement_range_end(&mut self, start: &Designator) -> bool {
+        self.complement_inner(start, true)
+    }
+
+    fn complement_inner(&mut self, other: &Designator, allows_multi_char_suffix: bool) -> bool {
         // 自身が空の場合、他が空もしくは単語の場合は補完対象外
         if self.is_empty() || other.is_empty() || other.is_word() {
-            return;
+            return false;
         }
 
+        let mut is_suffix_completed = false;
+
         // R1a,b のような参照名の場合、b は接尾辞として扱う
         // パーサーからは b は接頭辞だけの参照名として扱われているはずなので、接尾辞から補完していく
         // (接尾辞と接頭辞を入れ替える)
@@ -227,35 +677,51 @@ impl Designator {
         // 接尾辞の補完
         if other.suffix.is_some() {
             // 接頭辞が1文字かつアルファベットの場合だけ
-            if self.is_word()
-                && self.prefix.len() == 1
+            let is_single_char = self.prefix.chars().count() == 1
                 && self
                     .prefix
                     .chars()
                     .next()
-                    .is_some_and(|c| c.is_ascii_alphabetic())
-            {
-                self.suffix = self.prefix.pop(); // 1文字しかないので、pop 後 prefix は空
+                    .is_some_and(|c| c.is_ascii_alphabetic());
+            // 複数文字の場合は、大文字小文字が other の接尾辞と同じ場合だけ
+            let is_multi_char = allows_multi_char_suffix
+                && is_suffix(&self.prefix)
+                && self.prefix.chars().all(|c| c.is_ascii_lowercase())
+                    == other.is_lowercase_suffix();
+            if self.is_word() && (is_single_char || is_multi_char) {
+                // 接頭辞をすべて接尾辞に移すので、接頭辞は空になる
+                self.suffix = Some(std::mem::take(&mut self.prefix));
+                is_suffix_completed = true;
             }
             // 接頭辞と接尾辞が入れ替えられたら、数値も補完する
-            if self.suffix.is_some() && self.number == 0 {
+            if self.suffix.is_some() && !self.has_number() {
                 self.number = other.number;
+                self.width = other.width;
             }
         }
 
         // 数値は接尾辞があるときしか補完できない
 
         // 接頭辞の補完
-        // 接頭辞がなければ、other と同じに
-        if self.prefix.is_empty() {
+        // 接頭辞がなければ、other と同じに (階層も同じにする)
+        if self.prefix.is_empty() && self.path.is_empty() {
+            self.path = other.path.clone();
+            self.sign = other.sign;
             self.prefix = other.prefix.clone();
         }
+
+        is_suffix_completed
     }
 
     pub fn difference(&self, other: &Designator) -> Option<isize> {
         // 括弧は無視して、差分をとる
-        // prefix 違いは None
-        if self.prefix != other.prefix {
+        // 階層違い、prefix 違い、属性違い、ゼロ埋めの桁数違い (R001 と R1) は None
+        if self.path != other.path
+            || self.sign != other.sign
+            || self.prefix != other.prefix
+            || self.attribute != other.attribute
+            || !self.has_same_padding(other)
+        {
             return None;
         }
 
@@ -264,24 +730,48 @@ impl Designator {
             return None;
         }
 
-        if let Some(ss) = self.suffix {
+        if let Some(ss) = self.suffix_index() {
             // prefix + number + suffix
             if self.number != other.number {
                 // suffix がある場合 number は同じでないといけない
                 return None;
             }
             // suffix ありなしが同じことの確認は済んでいるので、unwrap は失敗しない
-            let os = other.suffix.unwrap();
+            let os = other.suffix_index().unwrap();
 
-            if ss.is_ascii_lowercase() != os.is_ascii_lowercase() {
+            if self.is_lowercase_suffix() != other.is_lowercase_suffix() {
                 // 大文字小文字が異なる場合は None
                 return None;
             }
 
-            Some(ss as isize - os as isize)
+            signed_difference(ss, os)
         } else {
             // prefix + number
-            Some(self.number as isize - other.number as isize)
+            signed_difference(self.number, other.number)
         }
     }
+
+    // 接尾辞をゲートとして、ゲート数 gates ごとに番号が繰り上がるものとした差分
+    // gates が 4 のとき U2A と U1D の差分は 1
+    pub fn gate_difference(&self, other: &Designator, gates: usize) -> Option<isize> {
+        if self.path != other.path
+            || self.sign != other.sign
+            || self.prefix != other.prefix
+            || self.attribute != other.attribute
+            || !self.has_same_padding(other)
+            || self.is_lowercase_suffix() != other.is_lowercase_suffix()
+        {
+            return None;
+        }
+
+        let position = |designator: &Designator| {
+            let index = designator.suffix_index().filter(|index| *index <= gates)?;
+            designator
+                .number
+                .checked_mul(gates)?
+                .checked_add(index - 1)
+                .and_then(|position| isize::try_from(position).ok())
+        };
+        Some(position(self)? - position(other)?)
+    }
 }
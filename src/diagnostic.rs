@@ -0,0 +1,94 @@
+use std::fmt;
+use std::ops::Range;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    Warning,
+    Error,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DiagnosticKind {
+    // 対応する括弧がない
+    UnbalancedParen,
+    // 範囲記号の左右が参照名でない、範囲記号が複数ある
+    InvalidRange,
+    // 範囲の終わりが始まりより小さい (R5~1)
+    ReversedRange,
+    // 範囲の左右で接頭辞が異なる (R1~C5)
+    RangeAcrossPrefixes,
+    // 範囲に含まれる参照名の数が上限を超える
+    RangeTooLarge(usize),
+    // 1文字の接頭辞を接尾辞として補完した (R1a,b)
+    SuffixCompletion,
+    // 同じ参照名が複数回現れる
+    Duplicate(String),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Diagnostic {
+    kind: DiagnosticKind,
+    // 元の入力文字列でのバイト位置
+    span: Range<usize>,
+}
+
+impl fmt::Display for DiagnosticKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            DiagnosticKind::UnbalancedParen => f.write_str("unbalanced parenthesis"),
+            DiagnosticKind::InvalidRange => f.write_str("invalid range"),
+            DiagnosticKind::ReversedRange => f.write_str("reversed range"),
+            DiagnosticKind::RangeAcrossPrefixes => f.write_str("range across different prefixes"),
+            DiagnosticKind::RangeTooLarge(count) => {
+                write!(f, "range contains too many designators: {}", count)
+            }
+            DiagnosticKind::SuffixCompletion => {
+                f.write_str("suffix completed from previous designator")
+            }
+            DiagnosticKind::Duplicate(designator) => {
+                write!(f, "duplicate designator: {}", designator)
+            }
+        }
+    }
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let severity = match self.severity() {
+            Severity::Warning => "warning",
+            Severity::Error => "error",
+        };
+        write!(
+            f,
+            "{}: {} at {}..{}",
+            severity, self.kind, self.span.start, self.span.end
+        )
+    }
+}
+
+impl std::error::Error for Diagnostic {}
+
+impl Diagnostic {
+    pub fn new(kind: DiagnosticKind, span: Range<usize>) -> Self {
+        Self { kind, span }
+    }
+
+    pub fn kind(&self) -> &DiagnosticKind {
+        &self.kind
+    }
+
+    pub fn span(&self) -> Range<usize> {
+        self.span.clone()
+    }
+
+    pub fn severity(&self) -> Severity {
+        match self.kind {
+            // 重複は結果に影響しないので警告
+            DiagnosticKind::Duplicate(_) => Severity::Warning,
+            // 接尾辞の補完は省略記法として許容しているので警告
+            DiagnosticKind::SuffixCompletion => Severity::Warning,
+            // それ以外は入力を推測で修正しているのでエラー
+            _ => Severity::Error,
+        }
+    }
+}
@@ -0,0 +1,152 @@
+use super::diagnostic::Diagnostic;
+use super::set::DesignatorSet;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+
+// 2つの参照名の集合の差分
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Diff {
+    added: DesignatorSet,
+    removed: DesignatorSet,
+    unchanged: DesignatorSet,
+}
+
+impl fmt::Display for Diff {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut sections: Vec<String> = Vec::new();
+        if !self.added.is_empty() {
+            sections.push(format!("added: {}", self.added));
+        }
+        if !self.removed.is_empty() {
+            sections.push(format!("removed: {}", self.removed));
+        }
+
+        f.write_str(&sections.join(", "))
+    }
+}
+
+impl Diff {
+    pub fn new(old: &DesignatorSet, new: &DesignatorSet) -> Self {
+        Self {
+            added: new.difference(old),
+            removed: old.difference(new),
+            unchanged: old.intersection(new),
+        }
+    }
+
+    pub fn added(&self) -> &DesignatorSet {
+        &self.added
+    }
+
+    pub fn removed(&self) -> &DesignatorSet {
+        &self.removed
+    }
+
+    pub fn unchanged(&self) -> &DesignatorSet {
+        &self.unchanged
+    }
+
+    // 追加、削除がなければ変更なし
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty()
+    }
+}
+
+pub fn diff(old: &str, new: &str) -> Result<Diff, Diagnostic> {
+    Ok(Diff::new(&old.parse()?, &new.parse()?))
+}
+
+// 部品番号間で移動した参照名
+#[derive(Debug, Clone, PartialEq)]
+pub struct Moved {
+    from: String,
+    to: String,
+    designators: DesignatorSet,
+}
+
+impl fmt::Display for Moved {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "moved: {} ({} -> {})",
+            self.designators, self.from, self.to
+        )
+    }
+}
+
+impl Moved {
+    pub fn from(&self) -> &str {
+        self.from.as_str()
+    }
+
+    pub fn to(&self) -> &str {
+        self.to.as_str()
+    }
+
+    pub fn designators(&self) -> &DesignatorSet {
+        &self.designators
+    }
+}
+
+// 部品番号ごとの差分
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct BomDiff {
+    lines: BTreeMap<String, Diff>,
+    moved: Vec<Moved>,
+}
+
+impl BomDiff {
+    pub fn lines(&self) -> &BTreeMap<String, Diff> {
+        &self.lines
+    }
+
+    pub fn line(&self, part_number: &str) -> Option<&Diff> {
+        self.lines.get(part_number)
+    }
+
+    pub fn moved(&self) -> &[Moved] {
+        self.moved.as_slice()
+    }
+}
+
+// 部品番号をキー、参照名の文字列を値とする BOM 同士の差分をとる
+pub fn diff_bom(
+    old: &BTreeMap<String, String>,
+    new: &BTreeMap<String, String>,
+) -> Result<BomDiff, Diagnostic> {
+    let part_numbers: BTreeSet<&String> = old.keys().chain(new.keys()).collect();
+
+    let mut lines: BTreeMap<String, Diff> = BTreeMap::new();
+    for part_number in part_numbers {
+        // 片方にしかない部品番号は空の集合と比較する
+        let old = match old.get(part_number) {
+            Some(s) => s.parse()?,
+            None => DesignatorSet::new(),
+        };
+        let new = match new.get(part_number) {
+            Some(s) => s.parse()?,
+            None => DesignatorSet::new(),
+        };
+        lines.insert(part_number.clone(), Diff::new(&old, &new));
+    }
+
+    // ある部品番号から削除され、別の部品番号に追加されたものは移動
+    let mut moved: Vec<Moved> = Vec::new();
+    for (from, from_diff) in &lines {
+        for (to, to_diff) in &lines {
+            if from == to {
+                continue;
+            }
+            let designators = from_diff.removed.intersection(&to_diff.added);
+            if !designators.is_empty() {
+                moved.push(Moved {
+                    from: from.clone(),
+                    to: to.clone(),
+                    designators,
+                });
+            }
+        }
+    }
+
+    Ok(BomDiff { lines, moved })
+}
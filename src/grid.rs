@@ -0,0 +1,274 @@
+use super::diagnostic::Diagnostic;
+use super::parser::Parser;
+use super::token::{COMMA, RANGE};
+use std::collections::BTreeSet;
+use std::fmt;
+use std::str::FromStr;
+
+// JEDEC の行記号 (I, O, Q, S, X, Z は使わない)
+// Y の次は AA, AB ... となる
+const ROW_ALPHABET: [char; 20] = [
+    'A', 'B', 'C', 'D', 'E', 'F', 'G', 'H', 'J', 'K', 'L', 'M', 'N', 'P', 'R', 'T', 'U', 'V', 'W',
+    'Y',
+];
+// 矩形の記号 (A1:C4)
+pub(crate) const RECTANGLE: char = ':';
+
+fn row_to_index(row: &str) -> Option<usize> {
+    if row.is_empty() {
+        return None;
+    }
+    row.chars().try_fold(0usize, |acc, c| {
+        let n = ROW_ALPHABET.iter().position(|r| *r == c)? + 1;
+        acc.checked_mul(ROW_ALPHABET.len())?.checked_add(n)
+    })
+}
+
+fn index_to_row(mut index: usize) -> String {
+    let mut row: Vec<char> = Vec::new();
+    while index > 0 {
+        index -= 1;
+        row.push(ROW_ALPHABET[index % ROW_ALPHABET.len()]);
+        index /= ROW_ALPHABET.len();
+    }
+    row.iter().rev().collect()
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ParseGridError {
+    // 行記号と列番号の組になっていない
+    InvalidCoordinate(String),
+    // 矩形の指定が不正 (A1:C4 の形になっていない、逆向き)
+    InvalidRectangle(String),
+    // 矩形の座標の数が上限を超えている
+    RectangleTooLarge(usize),
+    // 範囲などの解析エラー
+    Parse(Diagnostic),
+}
+
+impl fmt::Display for ParseGridError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ParseGridError::InvalidCoordinate(s) => write!(f, "invalid grid coordinate: {}", s),
+            ParseGridError::InvalidRectangle(s) => write!(f, "invalid grid rectangle: {}", s),
+            ParseGridError::RectangleTooLarge(count) => {
+                write!(f, "grid rectangle too large: {} coordinates", count)
+            }
+            ParseGridError::Parse(diagnostic) => write!(f, "{}", diagnostic),
+        }
+    }
+}
+
+impl std::error::Error for ParseGridError {}
+
+// 行優先と列優先の順番
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum GridOrder {
+    #[default]
+    RowMajor,
+    ColumnMajor,
+}
+
+// BGA のボール、コネクタのピンなどの格子状の座標 (A1, AB12)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct GridCoordinate {
+    // 行は 1 始まり (A = 1)
+    row: usize,
+    column: usize,
+}
+
+impl fmt::Display for GridCoordinate {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}{}", index_to_row(self.row), self.column)
+    }
+}
+
+impl FromStr for GridCoordinate {
+    type Err = ParseGridError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || ParseGridError::InvalidCoordinate(s.to_string());
+
+        let Some(num_start) = s.find(|c: char| c.is_ascii_digit()) else {
+            return Err(invalid());
+        };
+        let row = row_to_index(&s[..num_start]).ok_or_else(invalid)?;
+        // 列は 1 始まり
+        let column = usize::from_str(&s[num_start..])
+            .ok()
+            .filter(|column| *column > 0)
+            .ok_or_else(invalid)?;
+
+        Ok(Self { row, column })
+    }
+}
+
+impl GridCoordinate {
+    pub fn new(row: usize, column: usize) -> Self {
+        Self { row, column }
+    }
+
+    pub fn row(&self) -> usize {
+        self.row
+    }
+
+    pub fn row_name(&self) -> String {
+        index_to_row(self.row)
+    }
+
+    pub fn column(&self) -> usize {
+        self.column
+    }
+
+    fn key(&self, order: GridOrder) -> (usize, usize) {
+        match order {
+            GridOrder::RowMajor => (self.row, self.column),
+            GridOrder::ColumnMajor => (self.column, self.row),
+        }
+    }
+}
+
+pub fn sort(coordinates: &mut [GridCoordinate], order: GridOrder) {
+    coordinates.sort_by_key(|c| c.key(order));
+}
+
+// 矩形 (A1:C4) を展開する
+// 展開する前に、行数 × 列数が max_range_len を超えていないか確認する
+fn expand_rectangle(
+    s: &str,
+    max_range_len: Option<usize>,
+) -> Result<Vec<GridCoordinate>, ParseGridError> {
+    let invalid = || ParseGridError::InvalidRectangle(s.to_string());
+
+    let (start, end) = s.split_once(RECTANGLE).ok_or_else(invalid)?;
+    let start: GridCoordinate = start.parse().map_err(|_| invalid())?;
+    let end: GridCoordinate = end.parse().map_err(|_| invalid())?;
+    if start.row > end.row || start.column > end.column {
+        return Err(invalid());
+    }
+    // usize に収まらない場合は展開できない
+    let count = (end.row - start.row)
+        .checked_add(1)
+        .zip((end.column - start.column).checked_add(1))
+        .and_then(|(rows, columns)| rows.checked_mul(columns))
+        .ok_or(ParseGridError::RectangleTooLarge(usize::MAX))?;
+    if max_range_len.is_some_and(|max| count > max) {
+        return Err(ParseGridError::RectangleTooLarge(count));
+    }
+
+    // 確保できない大きさの場合も展開しない
+    let mut coordinates: Vec<GridCoordinate> = Vec::new();
+    coordinates
+        .try_reserve_exact(count)
+        .map_err(|_| ParseGridError::RectangleTooLarge(count))?;
+    coordinates.extend((start.row..=end.row).flat_map(|row| {
+        (start.column..=end.column).map(move |column| GridCoordinate { row, column })
+    }));
+    Ok(coordinates)
+}
+
+impl Parser {
+    // 行ごとの範囲 (A1~4) と矩形 (A1:C4) を解析する
+    pub fn parse_grid(&mut self) -> Result<Vec<GridCoordinate>, ParseGridError> {
+        let mut coordinates: Vec<GridCoordinate> = Vec::new();
+        for designator in self.try_parse().map_err(ParseGridError::Parse)? {
+            if designator.contains(RECTANGLE) {
+                coordinates.extend(expand_rectangle(&designator, self.options().max_range_len)?);
+            } else {
+                coordinates.push(designator.parse()?);
+            }
+        }
+
+        Ok(coordinates)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct GridBuildOptions {
+    pub order: GridOrder,
+    // 2行2列以上の矩形を A1:C4 のようにまとめるか
+    pub rectangles: bool,
+    // 範囲にまとめる最小の連続数
+    pub min_run_len: usize,
+}
+
+impl Default for GridBuildOptions {
+    fn default() -> Self {
+        Self {
+            order: GridOrder::RowMajor,
+            rectangles: true,
+            min_run_len: 3,
+        }
+    }
+}
+
+pub fn build_grid(coordinates: Vec<GridCoordinate>, options: &GridBuildOptions) -> String {
+    let mut rest: BTreeSet<GridCoordinate> = coordinates.into_iter().collect();
+    // 左上の座標と表記の組
+    let mut elements: Vec<(GridCoordinate, String)> = Vec::new();
+
+    if options.rectangles {
+        let mut coordinates: Vec<GridCoordinate> = rest.iter().copied().collect();
+        sort(&mut coordinates, GridOrder::RowMajor);
+
+        for start in coordinates {
+            if !rest.contains(&start) {
+                continue;
+            }
+            // 列方向に伸ばす
+            let mut end_column = start.column;
+            while let Some(column) = end_column
+                .checked_add(1)
+                .filter(|column| rest.contains(&GridCoordinate::new(start.row, *column)))
+            {
+                end_column = column;
+            }
+            // 行方向に伸ばす
+            let mut end_row = start.row;
+            while let Some(row) = end_row.checked_add(1).filter(|row| {
+                (start.column..=end_column)
+                    .all(|column| rest.contains(&GridCoordinate::new(*row, column)))
+            }) {
+                end_row = row;
+            }
+            if end_row == start.row || end_column == start.column {
+                continue;
+            }
+
+            for row in start.row..=end_row {
+                for column in start.column..=end_column {
+                    rest.remove(&GridCoordinate::new(row, column));
+                }
+            }
+            let end = GridCoordinate::new(end_row, end_column);
+            elements.push((start, format!("{}{}{}", start, RECTANGLE, end)));
+        }
+    }
+
+    // 残りは行優先なら行ごと、列優先なら列ごとに連続をまとめる
+    let mut coordinates: Vec<GridCoordinate> = rest.into_iter().collect();
+    sort(&mut coordinates, options.order);
+    let min_run_len = options.min_run_len.max(2);
+    let chunks = coordinates.chunk_by(|a, b| {
+        let (a, b) = (a.key(options.order), b.key(options.order));
+        a.0 == b.0 && a.1.checked_add(1) == Some(b.1)
+    });
+    for chunk in chunks {
+        let (first, last) = (chunk[0], chunk[chunk.len() - 1]);
+        if chunk.len() < min_run_len {
+            elements.extend(chunk.iter().map(|c| (*c, c.to_string())));
+        } else if options.order == GridOrder::RowMajor {
+            elements.push((first, format!("{}{}{}", first, RANGE, last.column)));
+        } else {
+            // 列方向の連続は1列の矩形として表す
+            elements.push((first, format!("{}{}{}", first, RECTANGLE, last)));
+        }
+    }
+
+    elements.sort_by_key(|(c, _)| c.key(options.order));
+    elements
+        .into_iter()
+        .map(|(_, s)| s)
+        .collect::<Vec<_>>()
+        .join(COMMA.to_string().as_str())
+}
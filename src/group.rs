@@ -0,0 +1,46 @@
+use super::token::{CLOSE_PAREN, OPEN_PAREN};
+
+// 括弧で囲まれた参照名のまとまりの種類
+// 括弧の種類ごとに別のまとまりとして扱い、出力も分ける
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Group {
+    // (R1) 実装しない部品 (DNP)
+    Paren,
+    // [R1] 代替部品
+    Bracket,
+    // {R1} バリアントごとの部品
+    Brace,
+}
+
+impl Group {
+    pub const ALL: [Group; 3] = [Group::Paren, Group::Bracket, Group::Brace];
+
+    pub fn open(self) -> char {
+        match self {
+            Group::Paren => OPEN_PAREN,
+            Group::Bracket => '[',
+            Group::Brace => '{',
+        }
+    }
+
+    pub fn close(self) -> char {
+        match self {
+            Group::Paren => CLOSE_PAREN,
+            Group::Bracket => ']',
+            Group::Brace => '}',
+        }
+    }
+
+    pub fn from_open(c: char) -> Option<Self> {
+        Self::ALL.into_iter().find(|group| group.open() == c)
+    }
+
+    pub fn from_close(c: char) -> Option<Self> {
+        Self::ALL.into_iter().find(|group| group.close() == c)
+    }
+
+    // 括弧で囲んだ文字列
+    pub fn enclose(self, s: &str) -> String {
+        format!("{}{}{}", self.open(), s, self.close())
+    }
+}
@@ -1,55 +1,183 @@
+use super::designator;
+use super::group::Group;
 use super::token;
 use crate::token::{Token, TokenWithSymbol};
-use std::iter::Peekable;
 
-pub struct Lexer {
-    iter: Peekable<std::vec::IntoIter<char>>,
+// 識別子以外として扱う文字の分類
+// いずれにも含まれない文字は識別子の一部となる
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CharClasses {
+    // 範囲記号
+    pub range: Vec<char>,
+    // 複数文字の範囲記号 ("..", "to", "thru")
+    // 英数字で始まるものは単語として、前後が区切りの場合にのみ範囲記号とし、大文字小文字を区別しない
+    // ".." はバス記法 (D[0..7]) と併用できない
+    pub range_operators: Vec<String>,
+    // 参照名の区切り
+    pub separator: Vec<char>,
+    // 丸括弧 (Group::Paren) として扱う文字
+    pub open_paren: Vec<char>,
+    pub close_paren: Vec<char>,
+    // 丸括弧以外に認識する括弧の種類
+    pub groups: Vec<Group>,
+    // Unicode の空白以外に空白として扱う文字
+    pub whitespace: Vec<char>,
 }
 
-fn get_char_token(c: char) -> Option<TokenWithSymbol> {
-    match c {
-        token::COMMA => Some(TokenWithSymbol::new(Token::Comma)),
-        token::CLOSE_PAREN => Some(TokenWithSymbol::new(Token::CloseParen)),
-        token::OPEN_PAREN => Some(TokenWithSymbol::new(Token::OpenParen)),
-        // 範囲記号は '~' が基本だが、'-', '～' も許容する
-        token::RANGE | '-' | '～' => Some(TokenWithSymbol::new(Token::Range(c))),
-        c if c.is_whitespace() => Some(TokenWithSymbol::new(Token::Whitespace)),
-        _ => None,
+impl Default for CharClasses {
+    fn default() -> Self {
+        Self {
+            // 範囲記号は '~' が基本だが、'-', '～' も許容する
+            range: vec![token::RANGE, '-', '～'],
+            range_operators: Vec::new(),
+            separator: vec![token::COMMA],
+            open_paren: vec![token::OPEN_PAREN],
+            close_paren: vec![token::CLOSE_PAREN],
+            groups: vec![Group::Bracket, Group::Brace],
+            whitespace: Vec::new(),
+        }
+    }
+}
+
+impl CharClasses {
+    fn get_char_token(&self, c: char) -> Option<TokenWithSymbol> {
+        let token = match c {
+            c if self.separator.contains(&c) => Token::Comma,
+            c if self.close_paren.contains(&c) => Token::CloseParen,
+            c if self.open_paren.contains(&c) => Token::OpenParen,
+            c if self.range.contains(&c) => Token::Range(c.to_string()),
+
+            c if c.is_whitespace() || self.whitespace.contains(&c) => Token::Whitespace,
+            c => return self.get_group_token(c),
+        };
+        Some(TokenWithSymbol::new(token))
     }
+
+    // 丸括弧以外の括弧
+    fn get_group_token(&self, c: char) -> Option<TokenWithSymbol> {
+        self.groups.iter().find_map(|group| match c {
+            _ if *group == Group::Paren => None,
+            c if c == group.open() => {
+                Some(TokenWithSymbol::new(Token::OpenParen).with_group(*group))
+            }
+            c if c == group.close() => {
+                Some(TokenWithSymbol::new(Token::CloseParen).with_group(*group))
+            }
+            _ => None,
+        })
+    }
+}
+
+pub struct Lexer {
+    chars: Vec<(usize, char)>,
+    pos: usize,
+    // 最後の文字の終わりの位置
+    end: usize,
+    classes: CharClasses,
 }
 
 impl Lexer {
+    #[cfg(test)]
     pub fn new(input: &str) -> Lexer {
+        Self::with_char_classes(input, CharClasses::default())
+    }
+
+    pub fn with_char_classes(input: &str, classes: CharClasses) -> Lexer {
+        Self::from_chars(input.char_indices().collect(), input.len(), classes)
+    }
+
+    // 文字と元の入力文字列でのバイト位置の組から作る
+    // 正規化で文字が変わっても、位置は元の入力文字列を指すようにできる
+    pub fn from_chars(chars: Vec<(usize, char)>, end: usize, mut classes: CharClasses) -> Lexer {
+        // 前後の空白を除く
+        let first = chars
+            .iter()
+            .position(|(_, c)| !c.is_whitespace())
+            .unwrap_or(chars.len());
+        let last = chars
+            .iter()
+            .rposition(|(_, c)| !c.is_whitespace())
+            .map_or(first, |i| i + 1);
+        let end = chars.get(last).map_or(end, |(i, _)| *i);
+        // 長い範囲記号から照合する
+        classes
+            .range_operators
+            .sort_by_key(|op| std::cmp::Reverse(op.chars().count()));
         Self {
-            iter: input
-                .trim()
-                .chars()
-                .collect::<Vec<char>>()
-                .into_iter()
-                .peekable(),
+            chars: chars[first..last].to_vec(),
+            pos: 0,
+            end,
+            classes,
         }
     }
 
     pub fn token(&mut self) -> Option<TokenWithSymbol> {
         let mut ident = String::new();
+        let mut start: Option<usize> = None;
 
-        while let Some(c) = self.iter.next() {
-            let tok = get_char_token(c);
-            if tok.is_some() {
-                return tok;
+        while let Some(&(i, c)) = self.chars.get(self.pos) {
+            // 視点記号 (=, +) で始まる階層参照名の '-' は識別子の一部 (=A1-R12)
+            let is_product_sign =
+                c == designator::PRODUCT_SIGN && ident.starts_with(designator::ASPECT_SIGNS);
+            // トークンの前までを識別子トークンとして返す
+            if let Some((len, tok)) = self.token_at(self.pos).filter(|_| !is_product_sign) {
+                if let Some(start) = start {
+                    return Some(
+                        TokenWithSymbol::new(Token::Identifier(ident)).with_span(start..i),
+                    );
+                }
+                self.pos += len;
+                return Some(tok.with_span(i..self.position(self.pos)));
             }
 
             // トークンでないものは識別子の一部
             ident.push(c);
+            start.get_or_insert(i);
+            self.pos += 1;
+        }
 
-            // 次の文字がトークンの場合は識別子トークンを返す
-            // 終端は確実にトークンとなるようにホワイトスペースを返す
-            let c = *self.iter.peek().unwrap_or(&token::WHITESPACE);
-            if get_char_token(c).is_some() {
-                return Some(TokenWithSymbol::new(Token::Identifier(ident)));
-            }
+        // 終端までの識別子
+        start.map(|start| TokenWithSymbol::new(Token::Identifier(ident)).with_span(start..self.end))
+    }
+
+    // pos 番目の文字の元の入力文字列での位置
+    fn position(&self, pos: usize) -> usize {
+        self.chars.get(pos).map_or(self.end, |(i, _)| *i)
+    }
+
+    // pos から始まるトークンと、その文字数
+    fn token_at(&self, pos: usize) -> Option<(usize, TokenWithSymbol)> {
+        if let Some((len, op)) = self.range_operator_at(pos) {
+            return Some((len, TokenWithSymbol::new(Token::Range(op))));
         }
+        let (_, c) = self.chars[pos];
+        self.classes.get_char_token(c).map(|tok| (1, tok))
+    }
+
+    fn range_operator_at(&self, pos: usize) -> Option<(usize, String)> {
+        // 前後が区切り (文字のトークンか端) であるか
+        let is_boundary = |i: Option<usize>| {
+            i.and_then(|i| self.chars.get(i))
+                .is_none_or(|(_, c)| self.classes.get_char_token(*c).is_some())
+        };
 
-        None
+        self.classes.range_operators.iter().find_map(|op| {
+            let len = op.chars().count();
+            let text: String = self
+                .chars
+                .get(pos..pos + len)?
+                .iter()
+                .map(|(_, c)| c)
+                .collect();
+            let is_word = op.starts_with(|c: char| c.is_alphanumeric());
+            if is_word {
+                (text.eq_ignore_ascii_case(op)
+                    && is_boundary(pos.checked_sub(1))
+                    && is_boundary(Some(pos + len)))
+                .then_some((len, text))
+            } else {
+                (len > 0 && text == *op).then_some((len, text))
+            }
+        })
     }
 }
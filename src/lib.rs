@@ -1,15 +1,32 @@
 pub mod builder;
-mod designator;
+pub mod bus;
+pub mod designator;
+pub mod diagnostic;
+pub mod diff;
+pub mod grid;
+pub mod group;
 mod lexer;
+pub mod normalize;
 pub mod parser;
+pub mod range;
+pub mod set;
 mod token;
 
 #[cfg(test)]
 mod tests {
-    use super::builder;
+    use super::builder::{self, BuildOptions};
+    use super::bus::*;
+    use super::designator::*;
+    use super::diagnostic::*;
+    use super::diff::*;
+    use super::grid::*;
+    use super::group::*;
     use super::lexer::*;
+    use super::normalize::*;
+    use super::set::*;
     use super::token::*;
-    use crate::parser::Parser;
+    use crate::parser::{DescendingRange, ParseOptions, Parser};
+    use std::collections::BTreeMap;
 
     #[test]
     fn test_builder() {
@@ -34,6 +51,257 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_designator() {
+        let designator: Designator = "R10a".parse().unwrap();
+        assert_eq!(designator.prefix(), "R");
+        assert_eq!(designator.number(), 10);
+        assert_eq!(designator.suffix(), Some("a"));
+        assert!(!designator.has_paren());
+
+        let designator = Designator::try_from("(IC1)").unwrap();
+        assert_eq!(designator.prefix(), "IC");
+        assert!(designator.has_paren());
+        assert_eq!(designator.to_string(), "(IC1)".to_string());
+
+        // ゼロ埋めの桁数を保持する
+        let designator: Designator = "R007".parse().unwrap();
+        assert_eq!(designator.number(), 7);
+        assert_eq!(designator.width(), 3);
+        assert_eq!(designator.to_string(), "R007".to_string());
+        assert_ne!(designator, "R7".parse().unwrap());
+        assert!(designator < "R010".parse().unwrap());
+        assert_eq!("R0".parse::<Designator>().unwrap().to_string(), "R0");
+
+        // 階層参照名
+        let designator: Designator = "1A2R3".parse().unwrap();
+        assert!(designator.is_hierarchical());
+        assert_eq!(designator.path().len(), 2);
+        assert_eq!(designator.path()[1].designator().prefix(), "A");
+        assert_eq!(designator.prefix(), "R");
+        assert_eq!(designator.number(), 3);
+        assert_eq!(designator.to_string(), "1A2R3".to_string());
+        let designator: Designator = "=A1-R12".parse().unwrap();
+        assert_eq!(designator.path()[0].sign(), Some('='));
+        assert_eq!(designator.sign(), Some('-'));
+        assert_eq!(designator.to_string(), "=A1-R12".to_string());
+        // 上位の階層から順に並べる
+        assert!("A2.R1".parse::<Designator>().unwrap() > "A1.R5".parse().unwrap());
+        assert!("A10.R1".parse::<Designator>().unwrap() > "A2.R5".parse().unwrap());
+        // 接尾辞付きの参照名は階層参照名ではない
+        assert!(!"R1A".parse::<Designator>().unwrap().is_hierarchical());
+        // 区切り記号以外の記号を含む参照名も階層参照名ではない
+        for s in ["A1:C4", "A1/R2", "A1_R2"] {
+            let designator: Designator = s.parse().unwrap();
+            assert!(!designator.is_hierarchical(), "{}", s);
+            assert_eq!(designator.to_string(), s.to_string());
+        }
+
+        // 単語の参照名
+        let designator: Designator = "GND".parse().unwrap();
+        assert!(designator.is_word());
+
+        // 複数文字の接尾辞は Z の次が AA
+        let designator: Designator = "U1Z".parse().unwrap();
+        let next = designator.next().unwrap();
+        assert_eq!(next.suffix(), Some("AA"));
+        assert_eq!(next.difference(&designator), Some(1));
+        assert!(designator < next);
+        assert_eq!(next.prev().unwrap().to_string(), "U1Z".to_string());
+        // 大文字小文字が混在する場合は単語
+        let designator: Designator = "U1Ab".parse().unwrap();
+        assert!(designator.is_word());
+
+        // 空、番号のオーバーフローはエラー
+        assert_eq!("".parse::<Designator>(), Err(ParseDesignatorError::Empty));
+        assert_eq!("()".parse::<Designator>(), Err(ParseDesignatorError::Empty));
+        assert_eq!(
+            "R99999999999999999999999".parse::<Designator>(),
+            Err(ParseDesignatorError::NumberOverflow(
+                "99999999999999999999999".to_string()
+            ))
+        );
+    }
+
+    #[test]
+    fn test_builder_options() {
+        let designators: Vec<String> = ["R1", "R2", "R3", "R4", "R5", "R7", "R8", "(C1)", "(C2)"]
+            .iter()
+            .map(|s| s.to_string())
+            .collect();
+
+        // Altium 形式
+        let options = BuildOptions {
+            range_symbol: "-".to_string(),
+            repeat_prefix: true,
+            ..Default::default()
+        };
+        assert_eq!(
+            builder::build_with_options(designators.clone(), &options),
+            "R1-R5,R7,R8,\n(C1,C2)".to_string()
+        );
+
+        // KiCad 形式
+        let options = BuildOptions {
+            separator: ", ".to_string(),
+            group_separator: ", ".to_string(),
+            repeat_prefix: true,
+            min_run_len: usize::MAX,
+            ..Default::default()
+        };
+        assert_eq!(
+            builder::build_with_options(designators.clone(), &options),
+            "R1, R2, R3, R4, R5, R7, R8, (C1, C2)".to_string()
+        );
+
+        // 2つ以上で範囲にまとめる
+        let options = BuildOptions {
+            min_run_len: 2,
+            ..Default::default()
+        };
+        assert_eq!(
+            builder::build_with_options(designators, &options),
+            "R1~5,7~8,\n(C1~2)".to_string()
+        );
+    }
+
+    #[test]
+    fn test_builder_wrap() {
+        let designators: Vec<String> = [
+            "R1", "R2", "R3", "R5", "R7", "R10", "R11", "R12", "R13", "R14", "R15", "C1", "C3",
+        ]
+        .iter()
+        .map(|s| s.to_string())
+        .collect();
+
+        // 範囲の途中では改行しない
+        let options = BuildOptions {
+            max_width: Some(10),
+            ..Default::default()
+        };
+        let s = builder::build_with_options(designators.clone(), &options);
+        assert_eq!(s, "C1,3,R1~3,\n5,7,10~15".to_string());
+        assert!(s.lines().all(|line| line.chars().count() <= 10));
+
+        // 行頭に接頭辞を付ける
+        let options = BuildOptions {
+            separator: ", ".to_string(),
+            max_width: Some(12),
+            wrap_with_prefix: true,
+            ..Default::default()
+        };
+        let s = builder::build_with_options(designators.clone(), &options);
+        assert_eq!(s, "C1, 3, R1~3,\nR5, 7, 10~15".to_string());
+
+        // 折り返しても同じ参照名に戻せる
+        let mut parser = Parser::new(s.as_str());
+        let mut parsed = parser.parse();
+        let mut expected = designators;
+        parsed.sort();
+        expected.sort();
+        assert_eq!(parsed, expected);
+    }
+
+    #[test]
+    fn test_builder_chunks() {
+        let designators: Vec<String> = [
+            "R1", "R2", "R3", "R5", "R7", "R10", "R11", "R12", "C1", "C3", "(U1)", "(U2)", "(U4)",
+            "(U6)", "(U8)",
+        ]
+        .iter()
+        .map(|s| s.to_string())
+        .collect();
+
+        let chunks = builder::build_chunks(designators.clone(), 10, &BuildOptions::default());
+        assert_eq!(
+            chunks,
+            vec![
+                "C1,3,R1~3".to_string(),
+                "R5,7,10~12".to_string(),
+                "(U1,2,4,6)".to_string(),
+                "(U8)".to_string(),
+            ]
+        );
+        assert!(chunks.iter().all(|chunk| chunk.chars().count() <= 10));
+
+        // 各要素を個別に解析しても元に戻る
+        let mut parsed: Vec<String> = chunks
+            .iter()
+            .flat_map(|chunk| Parser::new(chunk).parse())
+            .collect();
+        let mut expected = designators;
+        parsed.sort();
+        expected.sort();
+        assert_eq!(parsed, expected);
+
+        // 括弧のグループも同じチャンクでは通常の区切りでつなぐ
+        let designators = vec!["R1".to_string(), "(C1)".to_string()];
+        let chunks = builder::build_chunks(designators, 40, &BuildOptions::default());
+        assert_eq!(chunks, vec!["R1,(C1)".to_string()]);
+    }
+
+    #[test]
+    fn test_bus() {
+        let options = ParseOptions {
+            bus: true,
+            ..Default::default()
+        };
+        let mut parser = Parser::with_options("D[0..3],ADDR[2:0],CLK,D4~D7", options.clone());
+        let nets = parser.parse();
+        assert_eq!(
+            nets,
+            vec![
+                "D0".to_string(),
+                "D1".to_string(),
+                "D2".to_string(),
+                "D3".to_string(),
+                "ADDR2".to_string(),
+                "ADDR1".to_string(),
+                "ADDR0".to_string(),
+                "CLK".to_string(),
+                "D4".to_string(),
+                "D5".to_string(),
+                "D6".to_string(),
+                "D7".to_string(),
+            ]
+        );
+
+        for (style, expected) in [
+            (BusStyle::KiCad, "ADDR0,1,2,CLK,D[0..7]"),
+            (BusStyle::Altium, "ADDR0,1,2,CLK,D[7..0]"),
+            (BusStyle::Verilog, "ADDR0,1,2,CLK,D[7:0]"),
+        ] {
+            let build_options = BuildOptions {
+                bus_style: Some(style),
+                min_run_len: 4,
+                ..Default::default()
+            };
+            let s = builder::build_with_options(nets.clone(), &build_options);
+            assert_eq!(s, expected.to_string());
+
+            // バス記法のまま解析できる
+            let mut parser = Parser::with_options(&s, options.clone());
+            assert_eq!(parser.parse().len(), nets.len());
+        }
+
+        // 桁数が変わる降順のバス
+        let mut parser = Parser::with_options("ADDR[15:0],D[10..8]", options.clone());
+        let nets = parser.parse();
+        assert_eq!(nets.len(), 19);
+        assert_eq!(nets[0], "ADDR15".to_string());
+        assert_eq!(nets[5], "ADDR10".to_string());
+        assert_eq!(nets[6], "ADDR9".to_string());
+        assert_eq!(nets[15], "ADDR0".to_string());
+        assert_eq!(
+            nets[16..],
+            ["D10".to_string(), "D9".to_string(), "D8".to_string()]
+        );
+
+        // バス記法を展開しない場合は識別子のまま
+        let mut parser = Parser::new("D[0..3]");
+        assert_eq!(parser.parse(), vec!["D[0..3]".to_string()]);
+    }
+
     #[test]
     fn test_parser() {
         // let input = r"R1-3,5,6,(C3)";
@@ -53,6 +321,1016 @@ mod tests {
         let mut parser = Parser::new(input);
         let designators = parser.parse();
         assert_eq!(designators.len(), 1);
+
+        // 複数文字の接尾辞の範囲
+        let mut parser = Parser::new("U1Y~AB");
+        assert_eq!(
+            parser.parse(),
+            vec![
+                "U1Y".to_string(),
+                "U1Z".to_string(),
+                "U1AA".to_string(),
+                "U1AB".to_string(),
+            ]
+        );
+        assert_eq!(
+            builder::build(vec![
+                "U1Y".to_string(),
+                "U1Z".to_string(),
+                "U1AA".to_string(),
+                "U1AB".to_string(),
+            ]),
+            "U1Y~1AB".to_string()
+        );
+
+        // ゼロ埋めされた参照名
+        let mut parser = Parser::new("R001~R003,C0098~0101");
+        let designators = parser.parse();
+        assert_eq!(
+            designators,
+            vec![
+                "R001".to_string(),
+                "R002".to_string(),
+                "R003".to_string(),
+                "C0098".to_string(),
+                "C0099".to_string(),
+                "C0100".to_string(),
+                "C0101".to_string(),
+            ]
+        );
+        assert_eq!(
+            builder::build(designators),
+            "C0098~0101,R001~003".to_string()
+        );
+
+        // 階層参照名は最下位の階層だけで範囲にする
+        let mut parser = Parser::new("A1.R1~3,A2.R1,2");
+        let designators = parser.parse();
+        assert_eq!(
+            designators,
+            vec![
+                "A1.R1".to_string(),
+                "A1.R2".to_string(),
+                "A1.R3".to_string(),
+                "A2.R1".to_string(),
+                "A2.R2".to_string(),
+            ]
+        );
+        assert_eq!(builder::build(designators), "A1.R1~3,A2.R1,2".to_string());
+
+        // 視点記号で始まる階層参照名の '-' は範囲記号ではない
+        let options = ParseOptions {
+            strict: true,
+            ..Default::default()
+        };
+        let mut parser = Parser::with_options("=A1-R12,=A1-R1~3,R5-7", options);
+        let designators = parser.try_parse().unwrap();
+        assert_eq!(
+            designators,
+            vec![
+                "=A1-R12".to_string(),
+                "=A1-R1".to_string(),
+                "=A1-R2".to_string(),
+                "=A1-R3".to_string(),
+                "R5".to_string(),
+                "R6".to_string(),
+                "R7".to_string(),
+            ]
+        );
+        assert_eq!(builder::build(designators), "R5~7,=A1-R1~3,12".to_string());
+
+        // 型付きの参照名として取り出す
+        let input = r"R1~3,(C1a,1b)";
+        let mut parser = Parser::new(input);
+        let designators = parser.parse_designators();
+        assert_eq!(designators.len(), 5);
+        assert_eq!(designators[2].number(), 3);
+        assert!(!designators[2].has_paren());
+        assert_eq!(designators[4].prefix(), "C");
+        assert_eq!(designators[4].suffix(), Some("b"));
+        assert!(designators[4].has_paren());
+    }
+
+    #[test]
+    fn test_unicode_designator() {
+        // 括弧内の多バイト文字
+        for (s, prefix, number, has_paren) in [
+            ("(抵抗1)", "抵抗", 1, true),
+            ("(Ω12)", "Ω", 12, true),
+            ("μ3", "μ", 3, false),
+            ("(コネクタ10)", "コネクタ", 10, true),
+            ("(抵抗)", "抵抗", 0, true),
+        ] {
+            let designator = s.parse::<Designator>().unwrap();
+            assert_eq!(designator.prefix(), prefix, "{}", s);
+            assert_eq!(designator.number(), number, "{}", s);
+            assert_eq!(designator.has_paren(), has_paren, "{}", s);
+            assert_eq!(designator.to_string(), s);
+        }
+        // 接頭辞の比較
+        let a = "抵抗1".parse::<Designator>().unwrap();
+        let b = "抵抗5".parse::<Designator>().unwrap();
+        assert_eq!(b.difference(&a), Some(4));
+        assert_eq!(b.to_omitted_string(&a), "5".to_string());
+        let c = "Ω5".parse::<Designator>().unwrap();
+        assert_eq!(c.difference(&a), None);
+        assert_eq!(c.to_omitted_string(&a), "Ω5".to_string());
+
+        // 字句解析から出力まで
+        let input = "抵抗1~3,5 (Ω1,2),μ1";
+        let designators = Parser::new(input).parse();
+        assert_eq!(
+            designators,
+            vec![
+                "抵抗1".to_string(),
+                "抵抗2".to_string(),
+                "抵抗3".to_string(),
+                "抵抗5".to_string(),
+                "(Ω1)".to_string(),
+                "(Ω2)".to_string(),
+                "μ1".to_string(),
+            ]
+        );
+        let s = builder::build(designators.clone());
+        assert_eq!(s, "μ1,抵抗1~3,5,\n(Ω1,2)".to_string());
+        assert_eq!(Parser::new(&s).parse().len(), designators.len());
+
+        // 診断の位置はバイト単位
+        let input = "抵抗5~3";
+        let (_, diagnostics) = Parser::new(input).parse_with_diagnostics();
+        assert_eq!(diagnostics[0].kind(), &DiagnosticKind::ReversedRange);
+        assert_eq!(&input[diagnostics[0].span()], "抵抗5~3");
+
+        // 集合
+        let set: DesignatorSet = "抵抗1~3,抵抗2~5".parse().unwrap();
+        assert_eq!(set.len(), 5);
+        assert_eq!(set.to_string(), "抵抗1~5".to_string());
+    }
+
+    #[test]
+    fn test_group() {
+        let input = "R1~3 (R4,5) [R6~8],{C1},[C2]";
+        let designators = Parser::new(input).parse_designators();
+        let groups: Vec<Option<Group>> = designators.iter().map(|d| d.group()).collect();
+        assert_eq!(
+            groups,
+            vec![
+                None,
+                None,
+                None,
+                Some(Group::Paren),
+                Some(Group::Paren),
+                Some(Group::Bracket),
+                Some(Group::Bracket),
+                Some(Group::Bracket),
+                Some(Group::Brace),
+                Some(Group::Bracket),
+            ]
+        );
+        assert_eq!(designators[5].to_string(), "[R6]".to_string());
+        assert!(!designators[5].has_paren());
+        assert_eq!(designators[8].prefix(), "C");
+
+        // 種類ごとに別のまとまりとして出力する
+        let designators: Vec<String> = designators.iter().map(|d| d.to_string()).collect();
+        let s = builder::build(designators.clone());
+        assert_eq!(s, "R1~3,\n(R4,5),\n[C2,R6~8],\n{C1}".to_string());
+        assert_eq!(Parser::new(&s).parse(), {
+            let mut sorted = designators.clone();
+            sorted.sort_by_key(|s| s.parse::<Designator>().unwrap());
+            sorted
+        });
+
+        // 出力の順番を指定する
+        let options = BuildOptions {
+            group_order: vec![Group::Brace, Group::Bracket],
+            ..Default::default()
+        };
+        let s = builder::build_with_options(designators, &options);
+        assert_eq!(s, "R1~3,\n{C1},\n[C2,R6~8],\n(R4,5)".to_string());
+
+        // 種類が異なる閉じ括弧は対応しない
+        let input = "(R1],R2";
+        let (designators, diagnostics) = Parser::new(input).parse_with_diagnostics();
+        assert_eq!(designators, vec!["(R1]".to_string(), "R2".to_string()]);
+        assert_eq!(diagnostics.len(), 2);
+        assert!(diagnostics
+            .iter()
+            .all(|d| d.kind() == &DiagnosticKind::UnbalancedParen));
+
+        // 識別子に続く括弧はインライン括弧のまま
+        assert_eq!(Parser::new("D[0]").parse(), vec!["D[0]".to_string()]);
+
+        // 認識する括弧の種類を限定できる
+        let options = ParseOptions {
+            char_classes: CharClasses {
+                groups: Vec::new(),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let mut parser = Parser::with_options("[R1] {R2}", options);
+        assert_eq!(parser.parse(), vec!["[R1]".to_string(), "{R2}".to_string()]);
+
+        let set: DesignatorSet = "[R1~3],R2".parse().unwrap();
+        assert_eq!(set.len(), 4);
+        assert_eq!(set.to_string(), "R2,\n[R1~3]".to_string());
+    }
+
+    #[test]
+    fn test_attribute() {
+        let designator: Designator = "C3(10uF)".parse().unwrap();
+        assert_eq!(designator.prefix(), "C");
+        assert_eq!(designator.number(), 3);
+        assert_eq!(designator.attribute(), Some("10uF"));
+        assert_eq!(designator.to_string(), "C3(10uF)".to_string());
+        let designator: Designator = "(R1(DNP))".parse().unwrap();
+        assert!(designator.has_paren());
+        assert_eq!(designator.attribute(), Some("DNP"));
+        let designator: Designator = "U1(A(B))".parse().unwrap();
+        assert_eq!(designator.attribute(), Some("A(B)"));
+        // 属性が違う場合は範囲にならない
+        let a: Designator = "R1(DNP)".parse().unwrap();
+        let b: Designator = "R2".parse().unwrap();
+        assert_eq!(b.difference(&a), None);
+
+        // 範囲の後の属性は範囲全体に適用する
+        let designators = Parser::new("R1~3(DNP),R4,C1(10uF),2").parse_designators();
+        let attributes: Vec<Option<&str>> = designators.iter().map(|d| d.attribute()).collect();
+        assert_eq!(
+            attributes,
+            vec![
+                Some("DNP"),
+                Some("DNP"),
+                Some("DNP"),
+                None,
+                Some("10uF"),
+                None,
+            ]
+        );
+        assert_eq!(designators[2].to_string(), "R3(DNP)".to_string());
+        assert_eq!(designators[5].to_string(), "C2".to_string());
+
+        // 属性ごとにまとめる
+        let designators: Vec<String> = designators.iter().map(|d| d.to_string()).collect();
+        let s = builder::build(designators.clone());
+        assert_eq!(s, "C2,R4,C1(10uF),R1~3(DNP)".to_string());
+        let mut parsed = Parser::new(&s).parse();
+        let mut expected = designators;
+        parsed.sort();
+        expected.sort();
+        assert_eq!(parsed, expected);
+
+        let set: DesignatorSet = "R1~5(DNP),R3".parse().unwrap();
+        assert_eq!(set.len(), 6);
+        assert!(set.contains(&"R3(DNP)".parse().unwrap()));
+
+        // 空白で区切られた参照名の属性
+        let designators = Parser::new("R1(DNP) R2").parse_designators();
+        assert_eq!(designators.len(), 2);
+        assert_eq!(designators[0].to_string(), "R1(DNP)".to_string());
+        assert_eq!(designators[0].attribute(), Some("DNP"));
+        assert_eq!(designators[1].attribute(), None);
+
+        // 範囲の始まりの属性も範囲全体に適用する
+        let (designators, diagnostics) = Parser::new("R1(DNP)~R3").parse_with_diagnostics();
+        assert_eq!(
+            designators,
+            vec![
+                "R1(DNP)".to_string(),
+                "R2(DNP)".to_string(),
+                "R3(DNP)".to_string(),
+            ]
+        );
+        assert!(diagnostics.is_empty());
+    }
+
+    #[test]
+    fn test_diagnostics() {
+        let input = r" R1,(R2,R5~3,R1~C5,R1";
+        let mut parser = Parser::new(input);
+        let (designators, diagnostics) = parser.parse_with_diagnostics();
+        assert_eq!(designators.len(), 5);
+
+        let mut iter = diagnostics.iter();
+        let diagnostic = iter.next().unwrap();
+        assert_eq!(diagnostic.kind(), &DiagnosticKind::UnbalancedParen);
+        assert_eq!(&input[diagnostic.span()], "(");
+        let diagnostic = iter.next().unwrap();
+        assert_eq!(diagnostic.kind(), &DiagnosticKind::ReversedRange);
+        assert_eq!(&input[diagnostic.span()], "R5~3");
+        let diagnostic = iter.next().unwrap();
+        assert_eq!(diagnostic.kind(), &DiagnosticKind::RangeAcrossPrefixes);
+        assert_eq!(&input[diagnostic.span()], "R1~C5");
+        let diagnostic = iter.next().unwrap();
+        assert_eq!(
+            diagnostic.kind(),
+            &DiagnosticKind::Duplicate("R1".to_string())
+        );
+        assert_eq!(diagnostic.severity(), Severity::Warning);
+        assert_eq!(&input[diagnostic.span()], "R1");
+        assert_eq!(iter.next(), None);
+    }
+
+    #[test]
+    fn test_strict_parser() {
+        let options = ParseOptions {
+            strict: true,
+            ..Default::default()
+        };
+
+        let mut parser = Parser::with_options("R1~3,C1a,1b", options.clone());
+        assert_eq!(parser.try_parse().unwrap().len(), 5);
+
+        // 接尾辞の補完はエラー
+        let input = "R1a,b";
+        let mut parser = Parser::with_options(input, options.clone());
+        let err = parser.try_parse().unwrap_err();
+        assert_eq!(err.kind(), &DiagnosticKind::SuffixCompletion);
+        assert_eq!(&input[err.span()], "b");
+
+        // 接頭辞違いの範囲はエラー
+        let input = "R1~C5";
+        let mut parser = Parser::with_options(input, options.clone());
+        let err = parser.try_parse().unwrap_err();
+        assert_eq!(err.kind(), &DiagnosticKind::RangeAcrossPrefixes);
+
+        // 通常モードでは範囲とせず、そのまま1つの参照名とする
+        let mut parser = Parser::new(input);
+        assert_eq!(parser.try_parse(), Ok(vec!["R1~C5".to_string()]));
+
+        // 閉じられていないインライン括弧はエラー
+        for input in ["R1(", "R1(DNP", "R1(DNP,R2"] {
+            let mut parser = Parser::with_options(input, options.clone());
+            let err = parser.try_parse().unwrap_err();
+            assert_eq!(err.kind(), &DiagnosticKind::UnbalancedParen, "{}", input);
+            assert_eq!(err.span(), 2..3, "{}", input);
+        }
+        let (designators, diagnostics) = Parser::new("R1(DNP,R2").parse_with_diagnostics();
+        assert_eq!(designators, vec!["R1(DNP".to_string(), "R2".to_string()]);
+        assert_eq!(diagnostics.len(), 1);
+
+        // 閉じられたインライン括弧は診断を出さない
+        for input in ["R1(DNP)", "C3(10uF)", "R1~3(DNP),R4", "D[0]"] {
+            let (_, diagnostics) = Parser::new(input).parse_with_diagnostics();
+            assert_eq!(diagnostics, vec![], "{}", input);
+            let mut parser = Parser::with_options(input, options.clone());
+            assert!(parser.try_parse().is_ok(), "{}", input);
+        }
+    }
+
+    #[test]
+    fn test_descending_range() {
+        let input = "R3~1,C1";
+        // 既定では範囲とせず、診断を出す
+        let (designators, diagnostics) = Parser::new(input).parse_with_diagnostics();
+        assert_eq!(designators, vec!["R3~1".to_string(), "C1".to_string()]);
+        assert_eq!(diagnostics[0].kind(), &DiagnosticKind::ReversedRange);
+        assert!(Parser::new(input).try_parse().is_ok());
+
+        let parse = |descending: DescendingRange| {
+            let options = ParseOptions {
+                descending,
+                ..Default::default()
+            };
+            Parser::with_options(input, options).try_parse()
+        };
+        assert_eq!(
+            parse(DescendingRange::Ascending),
+            Ok(vec![
+                "R1".to_string(),
+                "R2".to_string(),
+                "R3".to_string(),
+                "C1".to_string(),
+            ])
+        );
+        assert_eq!(
+            parse(DescendingRange::Original),
+            Ok(vec![
+                "R3".to_string(),
+                "R2".to_string(),
+                "R1".to_string(),
+                "C1".to_string(),
+            ])
+        );
+        let err = parse(DescendingRange::Reject).unwrap_err();
+        assert_eq!(err.kind(), &DiagnosticKind::ReversedRange);
+        assert_eq!(&input[err.span()], "R3~1");
+
+        // 降順の範囲も展開せずに保持する
+        let options = ParseOptions {
+            descending: DescendingRange::Original,
+            ..Default::default()
+        };
+        let mut iter = Parser::with_options("R99999999~R1,C1", options)
+            .designators()
+            .unwrap();
+        assert_eq!(iter.checked_count(), Some(100000000));
+        assert_eq!(
+            iter.next().map(|d| d.to_string()),
+            Some("R99999999".to_string())
+        );
+        assert_eq!(
+            iter.next().map(|d| d.to_string()),
+            Some("R99999998".to_string())
+        );
+        let ranges = iter.into_ranges();
+        assert_eq!(ranges.len(), 2);
+        assert!(ranges[0].is_reversed());
+        assert_eq!(ranges[0].last().to_string(), "R1".to_string());
+
+        // 桁数が変わる降順の範囲
+        for (input, expected) in [
+            ("R10~R8", vec!["R10", "R9", "R8"]),
+            ("R12~R8", vec!["R12", "R11", "R10", "R9", "R8"]),
+            ("R100~R99", vec!["R100", "R99"]),
+            ("R100~R099", vec!["R100", "R099"]),
+            ("R101~R098", vec!["R101", "R100", "R099", "R098"]),
+        ] {
+            let options = ParseOptions {
+                descending: DescendingRange::Original,
+                ..Default::default()
+            };
+            let mut parser = Parser::with_options(input, options.clone());
+            let expected: Vec<String> = expected.iter().map(|s| s.to_string()).collect();
+            assert_eq!(parser.try_parse(), Ok(expected.clone()), "{}", input);
+            let iter = Parser::with_options(input, options).designators().unwrap();
+            assert_eq!(iter.checked_count(), Some(expected.len()), "{}", input);
+        }
+
+        // 上限は降順でも同じ
+        let options = ParseOptions {
+            descending: DescendingRange::Original,
+            max_range_len: Some(2),
+            ..Default::default()
+        };
+        let err = Parser::with_options(input, options)
+            .try_parse()
+            .unwrap_err();
+        assert_eq!(err.kind(), &DiagnosticKind::RangeTooLarge(3));
+    }
+
+    #[test]
+    fn test_gates() {
+        let gates = BTreeMap::from([("U".to_string(), 4)]);
+        let a: Designator = "U2A".parse().unwrap();
+        let b: Designator = "U1D".parse().unwrap();
+        assert_eq!(a.gate_difference(&b, 4), Some(1));
+        assert_eq!(b.gate_difference(&a, 4), Some(-1));
+        // ゲートの数を超える接尾辞
+        let c: Designator = "U1E".parse().unwrap();
+        assert_eq!(a.gate_difference(&c, 4), None);
+
+        let options = ParseOptions {
+            gates: gates.clone(),
+            ..Default::default()
+        };
+        let expected: Vec<String> = ["U1C", "U1D", "U2A", "U2B", "U2C", "U2D", "U3A", "U3B"]
+            .iter()
+            .map(|s| s.to_string())
+            .collect();
+        for input in ["U1C~U3B", "U1C~3B"] {
+            let mut parser = Parser::with_options(input, options.clone());
+            assert_eq!(parser.try_parse(), Ok(expected.clone()), "{}", input);
+        }
+        let mut parser = Parser::with_options("U1C~U3B", options.clone());
+        assert_eq!(parser.designators().unwrap().checked_count(), Some(8));
+
+        // ゲートの数が決まっていない場合は範囲にならない
+        let (designators, diagnostics) = Parser::new("U1A~U2D").parse_with_diagnostics();
+        assert_eq!(designators, vec!["U1A~U2D".to_string()]);
+        assert_eq!(diagnostics[0].kind(), &DiagnosticKind::InvalidRange);
+
+        // 降順、上限も同じように扱う
+        let mut parser = Parser::with_options(
+            "U2B~U1D",
+            ParseOptions {
+                descending: DescendingRange::Original,
+                ..options.clone()
+            },
+        );
+        assert_eq!(
+            parser.try_parse(),
+            Ok(vec![
+                "U2B".to_string(),
+                "U2A".to_string(),
+                "U1D".to_string()
+            ])
+        );
+        let mut parser = Parser::with_options(
+            "U1A~U3D",
+            ParseOptions {
+                max_range_len: Some(8),
+                ..options.clone()
+            },
+        );
+        assert_eq!(
+            parser.try_parse().unwrap_err().kind(),
+            &DiagnosticKind::RangeTooLarge(12)
+        );
+        // 大きな範囲でも展開する前に上限を確認する
+        let mut parser = Parser::with_options(
+            "U1A~U3000000D",
+            ParseOptions {
+                max_range_len: Some(1000),
+                ..options.clone()
+            },
+        );
+        assert_eq!(
+            parser.try_parse().unwrap_err().kind(),
+            &DiagnosticKind::RangeTooLarge(12000000)
+        );
+        // 上限がなくても番号ごとの範囲に分けずに保持する
+        let mut parser = Parser::with_options("U1A~U99999999D,U099D~U100B", options.clone());
+        let mut iter = parser.designators().unwrap();
+        assert_eq!(iter.checked_count(), Some(399999996 + 3));
+        assert_eq!(iter.nth(4).map(|d| d.to_string()), Some("U2A".to_string()));
+        let ranges = iter.into_ranges();
+        assert_eq!(ranges.len(), 2);
+        assert_eq!(ranges[0].gates(), Some(4));
+        let mut parser = Parser::with_options("U099D~U100B", options.clone());
+        assert_eq!(
+            parser.try_parse(),
+            Ok(vec![
+                "U099D".to_string(),
+                "U100A".to_string(),
+                "U100B".to_string()
+            ])
+        );
+
+        // 集合には番号ごとに分けて追加する
+        let mut parser = Parser::with_options("U1C~U3B", options.clone());
+        let mut set = DesignatorSet::new();
+        for range in parser.designators().unwrap().into_ranges() {
+            set.insert_range(range);
+        }
+        assert_eq!(set.len(), 8);
+        assert!(set.contains(&"U2D".parse().unwrap()));
+        assert_eq!(set.to_string(), "U1C,1D,2A~2D,3A,3B".to_string());
+
+        // ゲートをまたいでまとめる
+        let build_options = BuildOptions {
+            gates,
+            ..Default::default()
+        };
+        let s = builder::build_with_options(expected.clone(), &build_options);
+        assert_eq!(s, "U1C~3B".to_string());
+        let mut designators = expected.clone();
+        designators.push("U4A".to_string());
+        designators.push("U4C".to_string());
+        let s = builder::build_with_options(designators.clone(), &build_options);
+        assert_eq!(s, "U1C~3B,4A,4C".to_string());
+
+        // 接尾辞のない参照名は通常どおりまとめる
+        let numbers: Vec<String> = ["U1", "U2", "U3", "U5"]
+            .iter()
+            .map(|s| s.to_string())
+            .collect();
+        assert_eq!(
+            builder::build_with_options(numbers, &build_options),
+            "U1~3,5".to_string()
+        );
+        let mut parser = Parser::with_options(&s, options);
+        assert_eq!(parser.parse(), designators);
+    }
+
+    #[test]
+    fn test_builder_suffixes() {
+        let options = BuildOptions {
+            compress_suffixes: true,
+            ..Default::default()
+        };
+        let build = |input: &str| {
+            let designators = Parser::new(input).parse();
+            builder::build_with_options(designators, &options)
+        };
+        for (input, expected) in [
+            ("U1A,U1B,U1C,U1D", "U1A~D"),
+            ("U1A,U1B,U1C,U1D,U2A,U2C", "U1A~D,2A,C"),
+            ("R1a,R1b,R1c,R2a", "R1a~c,2a"),
+            ("U1Y,U1Z,U1AA,U1AB", "U1Y~AB"),
+            ("U1A,U1AA", "U1A,1AA"),
+            ("U1A,U1b", "U1A,1b"),
+            ("U1A~C,U1E,U3A", "U1A~C,E,3A"),
+            ("A1.U1A,A1.U1B,A1.U1C,A1.U1D", "A1.U1A~D"),
+            ("(U1A),(U1B)", "(U1A,B)"),
+            ("U1A(DNP),U1B(DNP),U1C(DNP)", "U1A~C(DNP)"),
+        ] {
+            let s = build(input);
+            assert_eq!(s, expected.to_string(), "{}", input);
+
+            // 元に戻せる
+            let mut expected = Parser::new(input).parse();
+            let mut parsed = Parser::new(&s).parse();
+            expected.sort();
+            parsed.sort();
+            assert_eq!(parsed, expected, "{}", s);
+        }
+
+        // 省略しない場合
+        let designators = Parser::new("U1A,U1B,U1C,U1D,U2A,U2C").parse();
+        assert_eq!(builder::build(designators), "U1A~1D,2A,2C".to_string());
+
+        // ゲートをまたぐ範囲
+        let options = BuildOptions {
+            gates: BTreeMap::from([("U".to_string(), 4)]),
+            ..options
+        };
+        let designators = Parser::new("U1A~D,U2A~D,U3A~C").parse();
+        assert_eq!(
+            builder::build_with_options(designators, &options),
+            "U1A~3C".to_string()
+        );
+    }
+
+    #[test]
+    fn test_order() {
+        let input = "R10,R2,R3,R4,C1,R1,(R6),(R7),R5,R2";
+        // 既定では入力の順番のまま
+        let designators = Parser::new(input).parse();
+        assert_eq!(designators.len(), 10);
+        assert_eq!(designators[0], "R10".to_string());
+
+        // 自然順に並べ、重複を除く
+        let options = ParseOptions {
+            sort: true,
+            ..Default::default()
+        };
+        let (sorted, diagnostics) = Parser::with_options(input, options).parse_with_diagnostics();
+        assert_eq!(
+            sorted,
+            vec![
+                "C1".to_string(),
+                "R1".to_string(),
+                "R2".to_string(),
+                "R3".to_string(),
+                "R4".to_string(),
+                "R5".to_string(),
+                "R10".to_string(),
+                "(R6)".to_string(),
+                "(R7)".to_string(),
+            ]
+        );
+        // 重複の診断は残る
+        assert_eq!(
+            diagnostics[0].kind(),
+            &DiagnosticKind::Duplicate("R2".to_string())
+        );
+
+        // 入力の順番を保ち、連続する部分だけをまとめる
+        let options = BuildOptions {
+            preserve_order: true,
+            ..Default::default()
+        };
+        let s = builder::build_with_options(designators.clone(), &options);
+        assert_eq!(s, "R10,2~4,C1,R1,\n(R6,7),R5,2".to_string());
+        assert_eq!(Parser::new(&s).parse(), designators);
+
+        // 並べ替える場合
+        assert_eq!(
+            builder::build(designators),
+            "C1,R1,2,2~5,10,\n(R6,7)".to_string()
+        );
+    }
+
+    #[test]
+    fn test_huge_range() {
+        // isize に収まらない番号の範囲
+        let input = "R9223372036854775807~R9223372036854775808";
+        let (designators, diagnostics) = Parser::new(input).parse_with_diagnostics();
+        assert_eq!(
+            designators,
+            vec![
+                "R9223372036854775807".to_string(),
+                "R9223372036854775808".to_string(),
+            ]
+        );
+        assert!(diagnostics.is_empty());
+        // 差分が isize に収まらない範囲
+        let input = "R0~R18446744073709551615";
+        let (_, diagnostics) = Parser::new(input).parse_with_diagnostics();
+        assert_eq!(diagnostics[0].kind(), &DiagnosticKind::InvalidRange);
+
+        // 上限がある場合は数を数えるだけで展開しない
+        let options = ParseOptions {
+            max_range_len: Some(1000),
+            ..Default::default()
+        };
+        let mut parser = Parser::with_options("R0~R9223372036854775807", options);
+        assert_eq!(
+            parser.try_parse().unwrap_err().kind(),
+            &DiagnosticKind::RangeTooLarge(9223372036854775808)
+        );
+
+        let designator: Designator = "R18446744073709551615".parse().unwrap();
+        assert_eq!(designator.next(), None);
+
+        // usize に収まらない数は数えない
+        let input = "R0~R9223372036854775807,C0~C9223372036854775807,L1";
+        let iter = Parser::new(input).designators().unwrap();
+        assert_eq!(iter.checked_count(), None);
+        assert_eq!(iter.size_hint(), (usize::MAX, None));
+        let set: DesignatorSet = input.parse().unwrap();
+        assert_eq!(set.len(), usize::MAX);
+    }
+
+    #[test]
+    fn test_designator_iter() {
+        let mut parser = Parser::new("R1~5,(C1~3),R10a~c");
+        let mut iter = parser.designators().unwrap();
+        assert_eq!(iter.checked_count(), Some(11));
+        assert_eq!(iter.next().map(|d| d.to_string()), Some("R1".to_string()));
+        assert_eq!(iter.next().map(|d| d.to_string()), Some("R2".to_string()));
+        assert_eq!(iter.checked_count(), Some(9));
+        assert_eq!(iter.nth(4).map(|d| d.to_string()), Some("(C2)".to_string()));
+        assert_eq!(iter.count(), 4);
+
+        // 展開せずに数える
+        let mut parser = Parser::new("R1~R99999999");
+        assert_eq!(parser.designators().unwrap().count(), 99999999);
+
+        // 上限を超える範囲はエラー
+        let options = ParseOptions {
+            max_range_len: Some(1000),
+            ..Default::default()
+        };
+        let mut parser = Parser::with_options("R1~R99999999", options.clone());
+        let err = parser.designators().unwrap_err();
+        assert_eq!(err.kind(), &DiagnosticKind::RangeTooLarge(99999999));
+        let mut parser = Parser::with_options("R1~R1000", options);
+        assert_eq!(parser.designators().unwrap().count(), 1000);
+    }
+
+    #[test]
+    fn test_set() {
+        let a: DesignatorSet = Parser::new("R1~10,C1,C2,U1a~d,(R20)")
+            .designators()
+            .unwrap()
+            .collect();
+        let b: DesignatorSet = Parser::new("R5~15,C2,C3,U1c~f")
+            .designators()
+            .unwrap()
+            .collect();
+
+        assert_eq!(a.len(), 17);
+        assert!(a.contains(&"R7".parse().unwrap()));
+        assert!(!a.contains(&"R20".parse().unwrap()));
+        assert!(a.contains(&"(R20)".parse().unwrap()));
+        assert_eq!(a.to_string(), "C1,2,R1~10,U1a~1d,\n(R20)".to_string());
+
+        assert_eq!(
+            a.union(&b).to_string(),
+            "C1~3,R1~15,U1a~1f,\n(R20)".to_string()
+        );
+        assert_eq!(
+            a.intersection(&b).to_string(),
+            "C2,R5~10,U1c,1d".to_string()
+        );
+        assert_eq!(
+            a.difference(&b).to_string(),
+            "C1,R1~4,U1a,1b,\n(R20)".to_string()
+        );
+        assert_eq!(
+            a.symmetric_difference(&b).to_string(),
+            "C1,3,R1~4,11~15,U1a,1b,1e,1f,\n(R20)".to_string()
+        );
+
+        // 範囲の途中を削除すると分割される
+        let mut set = a.clone();
+        assert!(set.remove(&"R5".parse().unwrap()));
+        assert!(!set.remove(&"R5".parse().unwrap()));
+        assert_eq!(set.len(), 16);
+        assert!(set.insert("R5".parse().unwrap()));
+        assert_eq!(set, a);
+
+        // 範囲は展開せずに出力する
+        let a: DesignatorSet = "R1~R9223372036854775807,C1,2".parse().unwrap();
+        let b: DesignatorSet = "R5".parse().unwrap();
+        assert_eq!(a.to_string(), "C1,2,R1~9223372036854775807".to_string());
+        assert_eq!(
+            a.difference(&b).to_string(),
+            "C1,2,R1~4,6~9223372036854775807".to_string()
+        );
+
+        // 桁数が変わる位置での削除
+        let mut set: DesignatorSet = "R1~R20".parse().unwrap();
+        assert!(set.remove(&"R10".parse().unwrap()));
+        assert_eq!(set.to_string(), "R1~9,11~20".to_string());
+        let a: DesignatorSet = "R95~R105".parse().unwrap();
+        let b: DesignatorSet = "R100".parse().unwrap();
+        assert_eq!(a.difference(&b).to_string(), "R95~99,101~105".to_string());
+        let mut set: DesignatorSet = "R098~R102".parse().unwrap();
+        assert_eq!(set.len(), 5);
+        assert!(set.contains(&"R100".parse().unwrap()));
+        assert!(set.remove(&"R099".parse().unwrap()));
+        assert!(set.remove(&"R100".parse().unwrap()));
+        assert_eq!(set.to_string(), "R098,101,102".to_string());
+    }
+
+    #[test]
+    fn test_mixed_width() {
+        // ゼロ埋めの桁数が異なる参照名は範囲にならない
+        let a: Designator = "R001".parse().unwrap();
+        let b: Designator = "R3".parse().unwrap();
+        assert_eq!(b.difference(&a), None);
+        let c: Designator = "U001D".parse().unwrap();
+        let d: Designator = "U2A".parse().unwrap();
+        assert_eq!(d.gate_difference(&c, 4), None);
+
+        // 前後の参照名は、ゼロ埋めされていなければ桁数が変わる
+        let r10: Designator = "R10".parse().unwrap();
+        assert_eq!(r10.prev().unwrap().to_string(), "R9".to_string());
+        assert_eq!(r10.prev().unwrap().width(), 1);
+        let r099: Designator = "R099".parse().unwrap();
+        let r100 = r099.next().unwrap();
+        assert_eq!(r100.to_string(), "R100".to_string());
+        assert_eq!(r100, "R100".parse().unwrap());
+        assert_eq!(r100.prev().unwrap().to_string(), "R99".to_string());
+
+        // ゼロ埋めの桁数に達する番号とは範囲になる
+        assert_eq!(r100.difference(&r099), Some(1));
+        let e: Designator = "R08".parse().unwrap();
+        let f: Designator = "R12".parse().unwrap();
+        assert_eq!(f.difference(&e), Some(4));
+        let g: Designator = "U099D".parse().unwrap();
+        let h: Designator = "U100A".parse().unwrap();
+        assert_eq!(h.gate_difference(&g, 4), Some(1));
+        assert_eq!(
+            Parser::new("R098~R102").parse(),
+            vec!["R098", "R099", "R100", "R101", "R102"]
+        );
+        assert_eq!(
+            builder::build(Parser::new("R098~R102").parse()),
+            "R098~102".to_string()
+        );
+        assert_eq!(Parser::new("R001~R100").parse().len(), 100);
+
+        // ゼロ埋めの桁数ごとにまとめる
+        let designators: Vec<String> = ["R001", "R002", "R003", "R1", "R2", "R3"]
+            .iter()
+            .map(|s| s.to_string())
+            .collect();
+        assert_eq!(builder::build(designators), "R1~3,001~003".to_string());
+
+        // 集合
+        let mut set: DesignatorSet = "R1~5".parse().unwrap();
+        assert!(!set.remove(&"R003".parse().unwrap()));
+        assert_eq!(set.to_string(), "R1~5".to_string());
+        assert!(set.insert("R003".parse().unwrap()));
+        assert_eq!(set.len(), 6);
+        assert!(set.contains(&"R3".parse().unwrap()));
+        assert!(set.contains(&"R003".parse().unwrap()));
+        assert!(!set.contains(&"R002".parse().unwrap()));
+
+        // 差分
+        let d = diff("R001~R003", "R1~R3").unwrap();
+        assert_eq!(d.added().to_string(), "R1~3".to_string());
+        assert_eq!(d.removed().to_string(), "R001~003".to_string());
+        assert!(d.unchanged().is_empty());
+
+        // ゼロ埋めの桁数に達しても順に並び、出力して元に戻せる
+        let designators: Vec<String> = ["R100", "R098", "R101", "R099"]
+            .iter()
+            .map(|s| s.to_string())
+            .collect();
+        let s = builder::build(designators.clone());
+        assert_eq!(s, "R098~101".to_string());
+        let mut expected = designators;
+        expected.sort_by_key(|s| s.parse::<Designator>().unwrap());
+        assert_eq!(Parser::new(&s).parse(), expected);
+        let options = ParseOptions {
+            sort: true,
+            ..Default::default()
+        };
+        assert_eq!(
+            Parser::with_options("R100,R099,R098", options).parse(),
+            vec!["R098", "R099", "R100"]
+        );
+    }
+
+    #[test]
+    fn test_diff() {
+        let diff = diff("R1~13,C1~40", "R1~11,C1~45").unwrap();
+        assert_eq!(
+            diff.to_string(),
+            "added: C41~45, removed: R12,13".to_string()
+        );
+        assert_eq!(diff.unchanged().to_string(), "C1~40,R1~11".to_string());
+
+        let old: BTreeMap<String, String> = [
+            ("RES-10K".to_string(), "R1~10".to_string()),
+            ("RES-1K".to_string(), "R11,R12".to_string()),
+        ]
+        .into();
+        let new: BTreeMap<String, String> = [
+            ("RES-10K".to_string(), "R1~8".to_string()),
+            ("RES-1K".to_string(), "R9~12".to_string()),
+            ("CAP-1U".to_string(), "C1".to_string()),
+        ]
+        .into();
+        let bom_diff = diff_bom(&old, &new).unwrap();
+        assert_eq!(
+            bom_diff.line("RES-10K").unwrap().to_string(),
+            "removed: R9,10".to_string()
+        );
+        assert_eq!(
+            bom_diff.line("RES-1K").unwrap().to_string(),
+            "added: R9,10".to_string()
+        );
+        assert_eq!(
+            bom_diff.line("CAP-1U").unwrap().to_string(),
+            "added: C1".to_string()
+        );
+
+        let moved = bom_diff.moved();
+        assert_eq!(moved.len(), 1);
+        assert_eq!(moved[0].from(), "RES-10K");
+        assert_eq!(moved[0].to(), "RES-1K");
+        assert_eq!(moved[0].designators().to_string(), "R9,10".to_string());
+
+        // 桁数が変わる位置で範囲が分かれる
+        let d = crate::diff::diff("R1~20", "R10~20").unwrap();
+        assert_eq!(d.to_string(), "removed: R1~9".to_string());
+        assert_eq!(d.unchanged().to_string(), "R10~20".to_string());
+        let d = crate::diff::diff("R95~R105", "R95~R99,R101~R105").unwrap();
+        assert_eq!(d.to_string(), "removed: R100".to_string());
+    }
+
+    #[test]
+    fn test_grid() {
+        let coordinate: GridCoordinate = "AA12".parse().unwrap();
+        assert_eq!(coordinate.row(), 21);
+        assert_eq!(coordinate.column(), 12);
+        assert_eq!(coordinate.to_string(), "AA12".to_string());
+        // JEDEC では I は使わない
+        assert!("I1".parse::<GridCoordinate>().is_err());
+        assert_eq!(
+            "H1".parse::<GridCoordinate>().unwrap().row() + 1,
+            "J1".parse::<GridCoordinate>().unwrap().row()
+        );
+
+        let mut parser = Parser::new("A1:C4,D1~3,E2");
+        let coordinates = parser.parse_grid().unwrap();
+        assert_eq!(coordinates.len(), 16);
+        assert!(coordinates.contains(&"C4".parse().unwrap()));
+
+        // 矩形と行ごとの範囲にまとめる
+        let s = build_grid(coordinates.clone(), &GridBuildOptions::default());
+        assert_eq!(s, "A1:C4,D1~3,E2".to_string());
+
+        // 列優先
+        let options = GridBuildOptions {
+            order: GridOrder::ColumnMajor,
+            rectangles: false,
+            ..Default::default()
+        };
+        let s = build_grid(coordinates.clone(), &options);
+        assert_eq!(s, "A1:D1,A2:E2,A3:D3,A4:C4".to_string());
+
+        // 元に戻せる
+        let mut parsed = Parser::new(&s).parse_grid().unwrap();
+        let mut expected = coordinates;
+        sort(&mut parsed, GridOrder::RowMajor);
+        sort(&mut expected, GridOrder::RowMajor);
+        assert_eq!(parsed, expected);
+
+        // 矩形も上限を超える場合は展開しない
+        let options = ParseOptions {
+            max_range_len: Some(1000),
+            ..Default::default()
+        };
+        let mut parser = Parser::with_options("A1:YY100000000", options);
+        assert_eq!(
+            parser.parse_grid(),
+            Err(ParseGridError::RectangleTooLarge(420 * 100000000))
+        );
+
+        // 桁あふれする矩形
+        let mut parser = Parser::new("A1:B18446744073709551615");
+        assert_eq!(
+            parser.parse_grid(),
+            Err(ParseGridError::RectangleTooLarge(usize::MAX))
+        );
+        let mut parser = Parser::new("A1:A18446744073709551615");
+        assert!(matches!(
+            parser.parse_grid(),
+            Err(ParseGridError::RectangleTooLarge(_))
+        ));
+        let coordinate: GridCoordinate = "A18446744073709551615".parse().unwrap();
+        assert_eq!(
+            build_grid(vec![coordinate], &GridBuildOptions::default()),
+            "A18446744073709551615".to_string()
+        );
+
+        // 列は 1 始まり
+        assert!("A0".parse::<GridCoordinate>().is_err());
+        let mut parser = Parser::new("A0:A18446744073709551615");
+        assert_eq!(
+            parser.parse_grid(),
+            Err(ParseGridError::InvalidRectangle(
+                "A0:A18446744073709551615".to_string()
+            ))
+        );
     }
 
     #[test]
@@ -79,13 +1357,187 @@ mod tests {
             lexer.token(),
             Some(TokenWithSymbol::new(Token::Identifier("R4".to_string())))
         );
-        assert_eq!(lexer.token(), Some(TokenWithSymbol::new(Token::Range('-'))));
+        assert_eq!(
+            lexer.token(),
+            Some(TokenWithSymbol::new(Token::Range("-".to_string())))
+        );
         assert_eq!(
             lexer.token(),
             Some(TokenWithSymbol::new(Token::Identifier("9".to_string())))
         );
     }
 
+    #[test]
+    fn test_char_classes() {
+        // '-' を識別子の一部とし、範囲記号は '~' のみとする
+        let options = ParseOptions {
+            char_classes: CharClasses {
+                range: vec!['~'],
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let mut parser = Parser::with_options("J1-3,J1-4 P2-R5~7", options.clone());
+        assert_eq!(
+            parser.parse(),
+            vec![
+                "J1-3".to_string(),
+                "J1-4".to_string(),
+                "P2-R5".to_string(),
+                "P2-R6".to_string(),
+                "P2-R7".to_string(),
+            ]
+        );
+        // 既定では '-' は範囲記号
+        let mut parser = Parser::new("J1-3");
+        assert_eq!(
+            parser.parse(),
+            vec!["J1".to_string(), "J2".to_string(), "J3".to_string()]
+        );
+
+        // 区切りや括弧の文字も追加できる
+        let options = ParseOptions {
+            char_classes: CharClasses {
+                separator: vec![',', ';'],
+                open_paren: vec!['(', '['],
+                close_paren: vec![')', ']'],
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let mut parser = Parser::with_options("R1;R2,[R3]", options);
+        assert_eq!(
+            parser.parse(),
+            vec!["R1".to_string(), "R2".to_string(), "(R3)".to_string()]
+        );
+
+        let classes = CharClasses {
+            whitespace: vec!['_'],
+            ..Default::default()
+        };
+        let mut lexer = Lexer::with_char_classes("R1_R2", classes);
+        assert_eq!(
+            lexer.token(),
+            Some(TokenWithSymbol::new(Token::Identifier("R1".to_string())))
+        );
+        assert_eq!(lexer.token(), Some(TokenWithSymbol::new(Token::Whitespace)));
+    }
+
+    #[test]
+    fn test_range_operators() {
+        let options = ParseOptions {
+            char_classes: CharClasses {
+                range_operators: vec!["..".to_string(), "to".to_string(), "thru".to_string()],
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let expected = vec![
+            "R1".to_string(),
+            "R2".to_string(),
+            "R3".to_string(),
+            "C1".to_string(),
+        ];
+        for input in [
+            "R1..R3,C1",
+            "R1..3 C1",
+            "R1 to R3,C1",
+            "R1 TO 3,C1",
+            "R1 thru R3,C1",
+            "R1 ~ R3,C1",
+        ] {
+            let mut parser = Parser::with_options(input, options.clone());
+            assert_eq!(parser.parse(), expected, "{}", input);
+        }
+
+        // 単語の一部は範囲記号にならない
+        let mut parser = Parser::with_options("TP1,TO1,R1to3", options.clone());
+        assert_eq!(
+            parser.parse(),
+            vec!["TP1".to_string(), "TO1".to_string(), "R1to3".to_string()]
+        );
+
+        // 範囲の検証は '~' と同じ
+        let mut parser = Parser::with_options("R1..C3", options.clone());
+        let (designators, diagnostics) = parser.parse_with_diagnostics();
+        assert_eq!(designators, vec!["R1..C3".to_string()]);
+        assert_eq!(diagnostics[0].kind(), &DiagnosticKind::RangeAcrossPrefixes);
+        assert_eq!(diagnostics[0].span(), 0..6);
+
+        let mut lexer = Lexer::with_char_classes("R1..R3", options.char_classes);
+        assert_eq!(
+            lexer.token(),
+            Some(TokenWithSymbol::new(Token::Identifier("R1".to_string())))
+        );
+        let token = lexer.token().unwrap();
+        assert_eq!(token, TokenWithSymbol::new(Token::Range("..".to_string())));
+        assert_eq!(token.span(), 2..4);
+    }
+
+    #[test]
+    fn test_normalize() {
+        assert_eq!(
+            normalize("Ｒ１０～Ｒ１２、（Ｃ１）・Ｌ１"),
+            "R10~R12,(C1),L1"
+        );
+        assert_eq!(normalize("R1\u{3000}R2〜4"), "R1 R2~4");
+
+        let options = ParseOptions {
+            normalize: true,
+            ..Default::default()
+        };
+        let input = "Ｒ１～３、Ｃ１\u{3000}（Ｌ１）";
+        let mut parser = Parser::with_options(input, options.clone());
+        assert_eq!(
+            parser.parse(),
+            vec![
+                "R1".to_string(),
+                "R2".to_string(),
+                "R3".to_string(),
+                "C1".to_string(),
+                "(L1)".to_string(),
+            ]
+        );
+        // 正規化しない場合は識別子のまま
+        assert_eq!(
+            Parser::new("Ｒ１、Ｒ２").parse(),
+            vec!["Ｒ１、Ｒ２".to_string()]
+        );
+
+        // 診断の位置は正規化後の文字列を指す
+        let input = "Ｒ１、Ｒ１";
+        let mut parser = Parser::with_options(input, options.clone());
+        let (_, diagnostics) = parser.parse_with_diagnostics();
+        assert_eq!(
+            diagnostics[0].kind(),
+            &DiagnosticKind::Duplicate("R1".to_string())
+        );
+        assert_eq!(&normalize(input)[diagnostics[0].span()], "R1");
+
+        // 元の表記を残す場合は、元の入力文字列を指し、元の表記で示す
+        let options = ParseOptions {
+            keep_original_spelling: true,
+            ..options
+        };
+        let mut parser = Parser::with_options(input, options.clone());
+        let (designators, diagnostics) = parser.parse_with_diagnostics();
+        assert_eq!(designators, vec!["R1".to_string(), "R1".to_string()]);
+        assert_eq!(&input[diagnostics[0].span()], "Ｒ１");
+        assert_eq!(
+            diagnostics[0].kind(),
+            &DiagnosticKind::Duplicate("Ｒ１".to_string())
+        );
+        // 範囲の中の重複は、範囲の表記ではなく参照名で示す
+        let input = "Ｒ１、Ｒ１～３";
+        let mut parser = Parser::with_options(input, options);
+        let (_, diagnostics) = parser.parse_with_diagnostics();
+        assert_eq!(&input[diagnostics[0].span()], "Ｒ１～３");
+        assert_eq!(
+            diagnostics[0].kind(),
+            &DiagnosticKind::Duplicate("R1".to_string())
+        );
+    }
+
     #[test]
     fn test_token() {
         // トークンのシンボルが正しいかのテスト
@@ -96,7 +1548,10 @@ mod tests {
             CLOSE_PAREN
         );
         assert_eq!(TokenWithSymbol::new(Token::OpenParen).symbol(), OPEN_PAREN);
-        assert_eq!(TokenWithSymbol::new(Token::Range('-')).symbol(), RANGE);
+        assert_eq!(
+            TokenWithSymbol::new(Token::Range("-".to_string())).symbol(),
+            RANGE
+        );
         assert_eq!(
             TokenWithSymbol::new(Token::Identifier("abc".to_string())).symbol(),
             IDENTIFIER
@@ -104,7 +1559,7 @@ mod tests {
 
         // 括弧で囲む
         let mut token = TokenWithSymbol::new(Token::Identifier("abc".to_string()));
-        token.parenthesize();
+        token.parenthesize(Group::Paren);
         assert_eq!(token.symbol(), IDENTIFIER.to_ascii_uppercase());
 
         // 連結する
@@ -0,0 +1,24 @@
+// 全角英数字や和文の句読点を、字句解析で扱える半角文字に揃える
+// NFKC に近いが、参照名の解析に必要な文字だけを1文字ずつ変換する
+// 1文字を1文字に変換するので、変換前後で文字の位置が対応する
+
+// 全角の '!' から '~' までと、半角の差
+const FULL_WIDTH_OFFSET: u32 = 0xFEE0;
+
+pub fn normalize(input: &str) -> String {
+    input.chars().map(normalize_char).collect()
+}
+
+pub fn normalize_char(c: char) -> char {
+    match c {
+        // 全角英数字、記号 (Ｒ１０, （）, ～)
+        '\u{FF01}'..='\u{FF5E}' => char::from_u32(c as u32 - FULL_WIDTH_OFFSET).unwrap_or(c),
+        // 和文の空白
+        '\u{3000}' => ' ',
+        // 読点と中黒は区切りとして扱う
+        '、' | '・' | '､' | '･' => ',',
+        // 波ダッシュは範囲記号
+        '〜' => '~',
+        _ => c,
+    }
+}
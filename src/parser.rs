@@ -1,21 +1,195 @@
+pub use super::lexer::CharClasses;
 use super::lexer::Lexer;
 use super::token::*;
-use crate::designator::Designator;
+use crate::bus;
+use crate::designator::{self, Designator};
+use crate::diagnostic::{Diagnostic, DiagnosticKind};
+use crate::group::Group;
+use crate::normalize;
+use crate::range::{DesignatorIter, DesignatorRange};
+use std::collections::{BTreeMap, BTreeSet};
+use std::ops::Range;
 use std::slice::IterMut;
 use std::vec::IntoIter;
 
+// 降順の範囲 (R5~R1) の扱い
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum DescendingRange {
+    // 範囲とせず、左右をそのまま参照名とする (診断を出す)
+    #[default]
+    Literal,
+    // 昇順に展開する (R1, R2, ..., R5)
+    Ascending,
+    // 記述された順に展開する (R5, R4, ..., R1)
+    Original,
+    // エラーとする
+    Reject,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct ParseOptions {
+    // 推測による修正をおこなわず、エラーとする
+    pub strict: bool,
+    // 1つの範囲に含められる参照名の最大数 (None は無制限)
+    pub max_range_len: Option<usize>,
+    // 降順の範囲の扱い
+    pub descending: DescendingRange,
+    // 接頭辞ごとのゲート (接尾辞) の数
+    // U: 4 の場合、U1A~U2D は U1A~U1D, U2A~U2D となる
+    pub gates: BTreeMap<String, usize>,
+    // バス記法 (D[0..7], ADDR[15:0]) を展開する
+    pub bus: bool,
+    // 字句解析での文字の分類
+    pub char_classes: CharClasses,
+    // 全角英数字や和文の句読点を字句解析の前に半角に揃える
+    pub normalize: bool,
+    // 正規化したときに、診断の位置と内容 (Duplicate の参照名) を正規化前の入力文字列で示す
+    // false の場合は normalize::normalize の結果での位置、表記となる
+    pub keep_original_spelling: bool,
+    // 展開した参照名を自然順に並べ、重複を除く (designators は対象外)
+    pub sort: bool,
+}
+
 pub struct Parser {
     lexer: Lexer,
+    options: ParseOptions,
+    // 元の表記を残す場合の正規化前の入力文字列
+    original: Option<String>,
 }
 
 impl Parser {
     pub fn new(input: &str) -> Self {
+        Self::with_options(input, ParseOptions::default())
+    }
+
+    pub fn with_options(input: &str, options: ParseOptions) -> Self {
+        let classes = options.char_classes.clone();
+        let lexer = if !options.normalize {
+            Lexer::with_char_classes(input, classes)
+        } else if options.keep_original_spelling {
+            let chars = input
+                .char_indices()
+                .map(|(i, c)| (i, normalize::normalize_char(c)))
+                .collect();
+            Lexer::from_chars(chars, input.len(), classes)
+        } else {
+            Lexer::with_char_classes(&normalize::normalize(input), classes)
+        };
+
+        let original =
+            (options.normalize && options.keep_original_spelling).then(|| input.to_string());
+
         Self {
-            lexer: Lexer::new(input),
+            lexer,
+            options,
+            original,
         }
     }
 
+    // 診断に含める参照名の表記
+    // 元の表記を残す場合、span が参照名1つだけを指していれば、その部分の元の表記とする
+    fn spelling(&self, designator: &Designator, span: &Range<usize>) -> String {
+        let text = designator.to_string();
+        self.original
+            .as_deref()
+            .and_then(|original| original.get(span.clone()))
+            .filter(|spelled| normalize::normalize(spelled) == text)
+            .map_or(text, String::from)
+    }
+
+    pub(crate) fn options(&self) -> &ParseOptions {
+        &self.options
+    }
+
     pub fn parse(&mut self) -> Vec<String> {
+        let (designators, _) = self.parse_with_diagnostics();
+        designators
+    }
+
+    pub fn parse_designators(&mut self) -> Vec<Designator> {
+        let mut diagnostics: Vec<Diagnostic> = Vec::new();
+        self.parse_inner(&mut diagnostics)
+    }
+
+    pub fn try_parse(&mut self) -> Result<Vec<String>, Diagnostic> {
+        let (designators, diagnostics) = self.parse_with_diagnostics();
+        self.check(diagnostics)?;
+
+        Ok(designators)
+    }
+
+    // 範囲を展開せずに返す
+    // 範囲は取り出すときに展開される
+    pub fn designators(&mut self) -> Result<DesignatorIter, Diagnostic> {
+        let mut diagnostics: Vec<Diagnostic> = Vec::new();
+        let ranges = self.parse_ranges(&mut diagnostics);
+        diagnostics.sort_by_key(|d| d.span().start);
+        self.check(diagnostics)?;
+
+        Ok(DesignatorIter::new(
+            ranges.into_iter().map(|(range, _)| range).collect(),
+        ))
+    }
+
+    pub fn parse_with_diagnostics(&mut self) -> (Vec<String>, Vec<Diagnostic>) {
+        let mut diagnostics: Vec<Diagnostic> = Vec::new();
+        let designators = self.parse_inner(&mut diagnostics);
+        // 入力文字列の位置順に並べる
+        diagnostics.sort_by_key(|d| d.span().start);
+
+        (
+            designators.into_iter().map(|d| d.to_string()).collect(),
+            diagnostics,
+        )
+    }
+
+    fn check(&self, diagnostics: Vec<Diagnostic>) -> Result<(), Diagnostic> {
+        let diagnostic = diagnostics.into_iter().find(|d| match d.kind() {
+            // 範囲の上限超えは常にエラー
+            DiagnosticKind::RangeTooLarge(_) => true,
+            DiagnosticKind::ReversedRange => {
+                self.options.strict || self.options.descending == DescendingRange::Reject
+            }
+            // 重複以外は推測による修正がおこなわれている
+            DiagnosticKind::Duplicate(_) => false,
+            _ => self.options.strict,
+        });
+
+        match diagnostic {
+            Some(diagnostic) => Err(diagnostic),
+            None => Ok(()),
+        }
+    }
+
+    fn parse_inner(&mut self, diagnostics: &mut Vec<Diagnostic>) -> Vec<Designator> {
+        let mut designators: Vec<Designator> = Vec::new();
+        // 重複チェック用
+        let mut seen: BTreeSet<Designator> = BTreeSet::new();
+
+        for (range, span) in self.parse_ranges(diagnostics) {
+            for designator in DesignatorIter::new(vec![range]) {
+                if !seen.insert(designator.clone()) {
+                    diagnostics.push(Diagnostic::new(
+                        DiagnosticKind::Duplicate(self.spelling(&designator, &span)),
+                        span.clone(),
+                    ));
+                }
+                designators.push(designator);
+            }
+        }
+
+        if self.options.sort {
+            designators.sort();
+            designators.dedup();
+        }
+
+        designators
+    }
+
+    fn parse_ranges(
+        &mut self,
+        diagnostics: &mut Vec<Diagnostic>,
+    ) -> Vec<(DesignatorRange, Range<usize>)> {
         let mut tokens: Vec<TokenWithSymbol> = Vec::new();
         // 先頭のカンマ、ホワイトスペースを除く
         if let Some(tok) = self.skip_start() {
@@ -32,9 +206,9 @@ impl Parser {
 
         // インライン括弧の処理
         // 識別子の後の開き括弧から閉じ括弧までの識別子化
-        replace_inline_parentheses(tokens.iter_mut());
+        replace_inline_parentheses(tokens.iter_mut(), diagnostics);
         // 対応する括弧がない括弧の識別子化
-        replace_invalid_parentheses(&mut tokens);
+        replace_invalid_parentheses(&mut tokens, diagnostics);
         // 括弧内の識別子への変換と括弧のホワイトスペース化
         // 括弧内の識別子をシンボルで判断できるようにした後、括弧をホワイトスペースに変換し、括弧を全てなくしまう
         // 削除するのではなくホワイトスペース化するのは区切りとして利用するため
@@ -42,7 +216,7 @@ impl Parser {
         // 範囲記号の左右以外のホワイトスペースをカンマへ変換
         replace_whitespace_to_comma(&mut tokens);
         // 不正な範囲記号の識別子化
-        replace_invalid_range_symbols(&mut tokens);
+        replace_invalid_range_symbols(&mut tokens, diagnostics);
         // トークン列の作り直し
         let mut tokens = rebuild(tokens.into_iter());
         // 範囲記号を前置記法に変換
@@ -53,10 +227,8 @@ impl Parser {
         // let rel = tokens.iter().map(|tok| tok.symbol()).collect::<String>();
         // println!("{}", rel);
 
-        // Designator に変換する
-        let designators: Vec<Designator> = convert_to_designators(tokens);
-
-        designators.into_iter().map(|d| d.to_string()).collect()
+        // Designator の範囲に変換する
+        convert_to_ranges(tokens, &self.options, diagnostics)
     }
 
     fn skip_start(&mut self) -> Option<TokenWithSymbol> {
@@ -94,75 +266,105 @@ impl Parser {
     }
 }
 
-fn replace_inline_parentheses(tokens: IterMut<TokenWithSymbol>) {
-    let mut depth: usize = 0;
+fn replace_inline_parentheses(tokens: IterMut<TokenWithSymbol>, diagnostics: &mut Vec<Diagnostic>) {
+    // 閉じられていないインライン括弧の開き括弧の位置
+    let mut opens: Vec<Range<usize>> = Vec::new();
     let mut last_symbol: Option<char> = None;
 
+    // 閉じられないまま終わった開き括弧は、識別子に含めたうえで診断を出す
+    let close_all = |opens: &mut Vec<Range<usize>>, diagnostics: &mut Vec<Diagnostic>| {
+        for span in opens.drain(..) {
+            diagnostics.push(Diagnostic::new(DiagnosticKind::UnbalancedParen, span));
+        }
+    };
+
     for token in tokens {
         match &token {
             // 識別子のあとが括弧の時がインライン括弧モードの始まり
             t if t.is_open_paren()
                 && last_symbol.is_some_and(|c| c.to_ascii_lowercase() == IDENTIFIER) =>
             {
-                depth += 1
+                opens.push(t.span())
             }
             // カンマでネストされたインライン括弧モードを含め終わらせる(最高優先度)
-            t if t.is_comma() => depth = 0,
+            t if t.is_comma() => close_all(&mut opens, diagnostics),
             _ => (),
         }
         // インライン括弧内の処理
-        if depth > 0 {
+        if !opens.is_empty() {
+            // 閉じ括弧で括弧の数を減らす
+            // 識別子に変換すると閉じ括弧か判断できなくなるので、先に確認する
+            if token.is_close_paren() {
+                opens.pop();
+            }
             // 識別子に変換
             token.convert_symbol_to_identifier();
             // 最後のシンボル記録
             last_symbol = Some(IDENTIFIER);
-            // 閉じ括弧で括弧の数を減らす
-            if token.is_close_paren() {
-                depth -= 1;
-            }
         } else {
             last_symbol = Some(token.symbol());
         }
     }
+
+    close_all(&mut opens, diagnostics);
 }
 
-fn replace_invalid_parentheses(tokens: &mut Vec<TokenWithSymbol>) {
-    let mut indices: Vec<usize> = Vec::new();
+fn replace_invalid_parentheses(tokens: &mut [TokenWithSymbol], diagnostics: &mut Vec<Diagnostic>) {
+    // 開き括弧の位置と種類
+    let mut indices: Vec<(usize, Option<Group>)> = Vec::new();
 
     for (i, token) in tokens.iter_mut().enumerate() {
         match token {
-            tok if tok.is_open_paren() => indices.push(i),
-            tok if tok.is_close_paren() && indices.pop().is_none() => {
+            tok if tok.is_open_paren() => indices.push((i, tok.group())),
+            // 種類が異なる閉じ括弧は対応しない
+            tok if tok.is_close_paren()
+                && indices
+                    .last()
+                    .is_some_and(|(_, group)| *group == tok.group()) =>
+            {
+                indices.pop();
+            }
+            tok if tok.is_close_paren() => {
+                diagnostics.push(Diagnostic::new(DiagnosticKind::UnbalancedParen, tok.span()));
                 tok.convert_symbol_to_identifier();
             }
             _ => (),
         }
     }
 
-    for i in indices {
+    for (i, _) in indices {
+        diagnostics.push(Diagnostic::new(
+            DiagnosticKind::UnbalancedParen,
+            tokens[i].span(),
+        ));
         tokens[i].convert_symbol_to_identifier();
     }
 }
 
 fn put_in_parentheses(tokens: IterMut<TokenWithSymbol>) {
-    let mut depth = 0;
+    // 入れ子の場合は最も内側の括弧の種類とする
+    let mut groups: Vec<Group> = Vec::new();
     for token in tokens {
         match token {
             tok if tok.is_open_paren() => {
-                depth += 1;
+                groups.extend(tok.group());
                 tok.convert_symbol_to_whitespace();
             }
             tok if tok.is_close_paren() => {
-                depth -= 1;
+                groups.pop();
                 tok.convert_symbol_to_whitespace();
             }
-            tok if tok.is_identifier() && depth > 0 => tok.parenthesize(),
+            tok if tok.is_identifier() => {
+                if let Some(group) = groups.last() {
+                    tok.parenthesize(*group);
+                }
+            }
             _ => (),
         }
     }
 }
 
-fn replace_whitespace_to_comma(tokens: &mut Vec<TokenWithSymbol>) {
+fn replace_whitespace_to_comma(tokens: &mut [TokenWithSymbol]) {
     // windows(3) を使った処理は、前後が必要になるため、先頭、末尾のホワイトスペースは置き換えられない
     // 先頭のホワイトスペースを変換
     tokens
@@ -199,7 +401,10 @@ fn replace_whitespace_to_comma(tokens: &mut Vec<TokenWithSymbol>) {
     }
 }
 
-fn replace_invalid_range_symbols(tokens: &mut Vec<TokenWithSymbol>) {
+fn replace_invalid_range_symbols(
+    tokens: &mut [TokenWithSymbol],
+    diagnostics: &mut Vec<Diagnostic>,
+) {
     let chunks = tokens.split_mut(|tok| tok.is_comma());
 
     for chunk in chunks {
@@ -212,14 +417,17 @@ fn replace_invalid_range_symbols(tokens: &mut Vec<TokenWithSymbol>) {
         let mut is_valid = contains_range_symbol_count == 1;
         if is_valid {
             // 間にあるホワイトスペースを無視して、[識別子][範囲記号][識別子](i~i) となっているかチェック
-            let mut iter = chunk.iter().filter(|tok| !tok.is_whitespace());
+            let mut iter = chunk.iter().filter(|tok| !tok.is_whitespace()).peekable();
             is_valid &= iter.next().is_some_and(|tok| tok.is_identifier());
+            // インライン括弧で分かれた識別子 (R1(DNP)~R3) は、後で1つの識別子にまとめられる
+            while iter.next_if(|tok| tok.is_identifier()).is_some() {}
             is_valid &= iter.next().is_some_and(|tok| tok.is_range());
             is_valid &= iter.next().is_some_and(|tok| tok.is_identifier());
         }
 
         if !is_valid {
             for tok in chunk.iter_mut().filter(|tok| tok.is_range()) {
+                diagnostics.push(Diagnostic::new(DiagnosticKind::InvalidRange, tok.span()));
                 tok.convert_symbol_to_identifier();
             }
         }
@@ -236,6 +444,7 @@ fn rebuild(iter: IntoIter<TokenWithSymbol>) -> Vec<TokenWithSymbol> {
             Some(p) if p.is_identifier() && token.is_identifier() => {
                 // 先にチェックしているのでエラーにはならない
                 let _ = p.merge_token(token.token());
+                p.extend_span(token.span());
             }
             _ => {
                 // シンボルに合わせて変換したうえで追加する
@@ -248,7 +457,7 @@ fn rebuild(iter: IntoIter<TokenWithSymbol>) -> Vec<TokenWithSymbol> {
     tokens
 }
 
-fn convert_prefix_notation(tokens: &mut Vec<TokenWithSymbol>) {
+fn convert_prefix_notation(tokens: &mut [TokenWithSymbol]) {
     let chunks = tokens
         .split_mut(|tok| tok.is_comma())
         .filter(|chunk| chunk.iter().any(|tok| tok.is_range()));
@@ -259,20 +468,109 @@ fn convert_prefix_notation(tokens: &mut Vec<TokenWithSymbol>) {
     }
 }
 
-fn convert_to_designators(tokens: Vec<TokenWithSymbol>) -> Vec<Designator> {
-    let mut designators: Vec<Designator> = Vec::new();
+fn chunk_span(chunk: &[TokenWithSymbol]) -> Range<usize> {
+    let start = chunk.iter().map(|tok| tok.span().start).min().unwrap_or(0);
+    let end = chunk.iter().map(|tok| tok.span().end).max().unwrap_or(0);
+    start..end
+}
+
+// バス記法を範囲に変換する
+// 降順 (ADDR[15:0]) の場合は、降順に展開する範囲とする
+fn convert_bus(
+    ident: &str,
+    max_range_len: Option<usize>,
+) -> Option<Result<DesignatorRange, DiagnosticKind>> {
+    let (inner, group) = designator::strip_group(ident);
+    let (name, first, last, width) = bus::split(inner)?;
+
+    let designator = |number: usize| {
+        let s = format!("{}{:0width$}", name, number, width = width);
+        let s = match group {
+            Some(group) => group.enclose(&s),
+            None => s,
+        };
+        Designator::from_lenient(s.as_str())
+    };
+    Some(to_range(
+        designator(first.min(last)),
+        designator(first.max(last)),
+        None,
+        first > last,
+        max_range_len,
+    ))
+}
+
+// start から end までの範囲に変換する
+// gates がある場合は、ゲート数ごとに番号が繰り上がる範囲 (U1A~U2D) とする
+// reverse の場合は、降順に展開する範囲とする
+fn to_range(
+    start: Designator,
+    end: Designator,
+    gates: Option<usize>,
+    reverse: bool,
+    max_range_len: Option<usize>,
+) -> Result<DesignatorRange, DiagnosticKind> {
+    let range = match gates {
+        Some(gates) => DesignatorRange::with_gates(start, end, gates),
+        None => DesignatorRange::new(start, end),
+    }
+    .ok_or(DiagnosticKind::InvalidRange)?;
+    let count = range.count();
+    if max_range_len.is_some_and(|max| count > max) {
+        return Err(DiagnosticKind::RangeTooLarge(count));
+    }
+
+    if reverse {
+        Ok(range.reversed())
+    } else {
+        Ok(range)
+    }
+}
+
+fn convert_to_ranges(
+    tokens: Vec<TokenWithSymbol>,
+    options: &ParseOptions,
+    diagnostics: &mut Vec<Diagnostic>,
+) -> Vec<(DesignatorRange, Range<usize>)> {
+    let max_range_len = options.max_range_len;
+    let mut ranges: Vec<(DesignatorRange, Range<usize>)> = Vec::new();
 
     for chunk in tokens.split(|tok| tok.is_comma()) {
         let mut iter = chunk.iter().peekable();
-        let prev = designators.last();
+        let prev = ranges.last().map(|(range, _)| range.last());
+        let span = chunk_span(chunk);
 
         match iter.next() {
             Some(tok) if tok.is_identifier() => {
-                let mut designator = Designator::from(tok.token().to_string().as_str());
-                if let Some(prev) = prev {
-                    designator.complement(prev);
+                let ident = tok.token().to_string();
+                // バス記法
+                let bus = if options.bus {
+                    convert_bus(&ident, max_range_len)
+                } else {
+                    None
+                };
+                match bus {
+                    Some(Ok(bus)) => {
+                        ranges.push((bus, span));
+                        continue;
+                    }
+                    Some(Err(kind)) => {
+                        diagnostics.push(Diagnostic::new(kind, span.clone()));
+                        let designator = Designator::from_lenient(ident.as_str());
+                        ranges.push((DesignatorRange::single(designator), span));
+                        continue;
+                    }
+                    None => (),
                 }
-                designators.push(designator);
+
+                let mut designator = Designator::from_lenient(ident.as_str());
+                if prev.is_some_and(|prev| designator.complement(prev)) {
+                    diagnostics.push(Diagnostic::new(
+                        DiagnosticKind::SuffixCompletion,
+                        tok.span(),
+                    ));
+                }
+                ranges.push((DesignatorRange::single(designator), span));
             }
             Some(tok) if tok.is_range() => {
                 // 範囲として不正なときに戻すためのベクタ
@@ -283,13 +581,17 @@ fn convert_to_designators(tokens: Vec<TokenWithSymbol>) -> Vec<Designator> {
                     iter.next();
                 }
                 // 一つめの参照名
-                let left = iter.next().unwrap().token().to_string();
+                let left_token = iter.next().unwrap();
+                let left = left_token.token().to_string();
                 elements.insert(0, left.clone());
                 // 範囲記号
                 elements.push(tok.token().to_string());
-                let mut left = Designator::from(left.as_str());
-                if let Some(prev) = prev {
-                    left.complement(prev);
+                let mut left = Designator::from_lenient(left.as_str());
+                if prev.is_some_and(|prev| left.complement(prev)) {
+                    diagnostics.push(Diagnostic::new(
+                        DiagnosticKind::SuffixCompletion,
+                        left_token.span(),
+                    ));
                 }
                 // ホワイトスペースか？
                 if iter.peek().is_some_and(|tok| tok.is_whitespace()) {
@@ -297,33 +599,73 @@ fn convert_to_designators(tokens: Vec<TokenWithSymbol>) -> Vec<Designator> {
                     iter.next();
                 }
                 // 2つめの参照名
-                let right = iter.next().unwrap().token().to_string();
+                let right_token = iter.next().unwrap();
+                let right = right_token.token().to_string();
                 elements.push(right.clone());
-                let mut right = Designator::from(right.as_str());
-                right.complement(&left);
+                let mut right = Designator::from_lenient(right.as_str());
+                if right.complement_range_end(&left) {
+                    diagnostics.push(Diagnostic::new(
+                        DiagnosticKind::SuffixCompletion,
+                        right_token.span(),
+                    ));
+                }
+
+                // 範囲の後の属性 (R1~5(DNP)) は範囲全体に適用する
+                match (left.attribute(), right.attribute()) {
+                    (None, Some(attribute)) => left = left.with_attribute(Some(attribute)),
+                    (Some(attribute), None) => right = right.with_attribute(Some(attribute)),
+                    _ => (),
+                }
+
+                // 番号が異なる接尾辞の範囲 (U1A~U2D) は、ゲートの数が決まっている場合だけ
+                let gates = options
+                    .gates
+                    .get(left.prefix())
+                    .copied()
+                    .filter(|_| left.number() != right.number())
+                    .filter(|_| left.suffix().is_some() && right.suffix().is_some());
+                let expand =
+                    |start, end, reverse| to_range(start, end, gates, reverse, max_range_len);
+                let difference = match gates {
+                    Some(gates) => right.gate_difference(&left, gates),
+                    None => right.difference(&left),
+                };
 
                 // 2つの差分をとって、二つめの方が大きい場合
-                if right.difference(&left).is_some_and(|diff| diff > 0) {
-                    designators.push(left.clone());
-                    while let Some(designator) = left.next() {
-                        if right.difference(&designator).is_some_and(|diff| diff > 0) {
-                            designators.push(designator.clone());
-                        } else {
-                            break;
+                // 上限を超える範囲は展開しない
+                let result = match difference {
+                    Some(diff) if diff > 0 => expand(left, right, false),
+                    Some(diff) if diff < 0 => match options.descending {
+                        DescendingRange::Ascending => expand(right, left, false),
+                        DescendingRange::Original => expand(right, left, true),
+                        DescendingRange::Literal | DescendingRange::Reject => {
+                            Err(DiagnosticKind::ReversedRange)
                         }
-                        left = designator;
+                    },
+                    None if right.prefix() != left.prefix() => {
+                        Err(DiagnosticKind::RangeAcrossPrefixes)
                     }
-                    designators.push(right);
-                } else {
-                    for elem in elements.split(|e| e.is_empty()) {
-                        let s = String::from_iter(elem.iter().map(|s| s.as_str()));
-                        designators.push(Designator::from(s.as_str()));
+                    _ => Err(DiagnosticKind::InvalidRange),
+                };
+                let kind = match result {
+                    Ok(range) => {
+                        ranges.push((range, span));
+                        continue;
                     }
+                    Err(kind) => kind,
+                };
+                diagnostics.push(Diagnostic::new(kind, span.clone()));
+                for elem in elements.split(|e| e.is_empty()) {
+                    let s = String::from_iter(elem.iter().map(|s| s.as_str()));
+                    ranges.push((
+                        DesignatorRange::single(Designator::from_lenient(s.as_str())),
+                        span.clone(),
+                    ));
                 }
             }
             _ => (),
         }
     }
 
-    designators
+    ranges
 }
@@ -0,0 +1,201 @@
+use super::designator::Designator;
+use std::vec::IntoIter;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct DesignatorRange {
+    start: Designator,
+    end: Designator,
+    // end から start に向かって降順に展開する
+    reverse: bool,
+    // 接尾辞をゲートとして番号をまたぐ範囲 (U1A~U2D) のゲートの数
+    gates: Option<usize>,
+}
+
+impl DesignatorRange {
+    pub fn new(start: Designator, end: Designator) -> Option<Self> {
+        // 終わりが始まり以上でないと範囲にならない
+        if end.difference(&start).is_some_and(|diff| diff >= 0) {
+            Some(Self {
+                start,
+                end,
+                reverse: false,
+                gates: None,
+            })
+        } else {
+            None
+        }
+    }
+
+    // ゲート数 gates ごとに番号が繰り上がる範囲 (U1A~U2D)
+    // 番号ごとの範囲に分けず、展開するときに番号を繰り上げる
+    pub fn with_gates(start: Designator, end: Designator, gates: usize) -> Option<Self> {
+        if end
+            .gate_difference(&start, gates)
+            .is_some_and(|diff| diff >= 0)
+        {
+            Some(Self {
+                start,
+                end,
+                reverse: false,
+                gates: Some(gates),
+            })
+        } else {
+            None
+        }
+    }
+
+    pub fn single(designator: Designator) -> Self {
+        Self {
+            start: designator.clone(),
+            end: designator,
+            reverse: false,
+            gates: None,
+        }
+    }
+
+    // 降順に展開する範囲にする
+    pub fn reversed(self) -> Self {
+        Self {
+            reverse: !self.reverse,
+            ..self
+        }
+    }
+
+    pub fn is_reversed(&self) -> bool {
+        self.reverse
+    }
+
+    pub fn gates(&self) -> Option<usize> {
+        self.gates
+    }
+
+    // 展開したときに最後になる参照名
+    pub fn last(&self) -> &Designator {
+        if self.reverse {
+            &self.start
+        } else {
+            &self.end
+        }
+    }
+
+    // 括弧を外した範囲
+    pub(crate) fn without_group(&self) -> Self {
+        Self {
+            start: self.start.without_group(),
+            end: self.end.without_group(),
+            ..self.clone()
+        }
+    }
+
+    pub fn start(&self) -> &Designator {
+        &self.start
+    }
+
+    pub fn end(&self) -> &Designator {
+        &self.end
+    }
+
+    // 展開せずに範囲内の参照名の数を計算する
+    pub fn count(&self) -> usize {
+        // 差分がとれない場合は、始まりだけの範囲として扱う
+        let difference = match self.gates {
+            Some(gates) => self.end.gate_difference(&self.start, gates),
+            None => self.end.difference(&self.start),
+        };
+        difference.map_or(1, |diff| diff.unsigned_abs() + 1)
+    }
+
+    // 範囲内で designator の次の参照名
+    fn step_forward(&self, designator: &Designator) -> Option<Designator> {
+        match self.gates {
+            Some(gates) => designator.next_gate(gates),
+            None => designator.next(),
+        }
+    }
+
+    // 範囲内で designator の前の参照名
+    // ゼロ埋めの桁数は範囲の始まりにそろえる (R100~R099)
+    fn step_back(&self, designator: &Designator) -> Option<Designator> {
+        let prev = match self.gates {
+            Some(gates) => designator.prev_gate(gates),
+            None => designator.prev(),
+        };
+        prev.map(|prev| prev.with_padding_of(&self.start))
+    }
+}
+
+// 範囲を必要になった時点で展開するイテレータ
+#[derive(Debug, Clone)]
+pub struct DesignatorIter {
+    ranges: IntoIter<DesignatorRange>,
+    current: Option<DesignatorRange>,
+}
+
+impl DesignatorIter {
+    pub(crate) fn new(ranges: Vec<DesignatorRange>) -> Self {
+        Self {
+            ranges: ranges.into_iter(),
+            current: None,
+        }
+    }
+
+    // 残りの参照名の数、usize に収まらない場合は None
+    pub fn checked_count(&self) -> Option<usize> {
+        self.current
+            .iter()
+            .chain(self.ranges.as_slice())
+            .try_fold(0usize, |acc, range| acc.checked_add(range.count()))
+    }
+
+    // 展開されていない残りの範囲を取り出す
+    pub(crate) fn into_ranges(self) -> Vec<DesignatorRange> {
+        self.current.into_iter().chain(self.ranges).collect()
+    }
+}
+
+impl Iterator for DesignatorIter {
+    type Item = Designator;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.current.is_none() {
+            self.current = Some(self.ranges.next()?);
+        }
+
+        let range = self.current.as_mut()?;
+        let designator = if range.reverse {
+            range.end.clone()
+        } else {
+            range.start.clone()
+        };
+        // 次 (降順では前) の参照名
+        let step = if range.count() <= 1 {
+            None
+        } else if range.reverse {
+            range.step_back(&designator)
+        } else {
+            range.step_forward(&designator)
+        };
+        match step {
+            Some(step) if range.reverse => range.end = step,
+            Some(step) => range.start = step,
+            None => self.current = None,
+        }
+
+        Some(designator)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        // 巨大な範囲が複数あって数えきれない場合は、上限なしとする
+        match self.checked_count() {
+            Some(len) => (len, Some(len)),
+            None => (usize::MAX, None),
+        }
+    }
+
+    fn count(self) -> usize {
+        match self.checked_count() {
+            Some(len) => len,
+            None => self.fold(0, |acc, _| acc + 1),
+        }
+    }
+}
@@ -0,0 +1,311 @@
+use super::builder::{self, BuildOptions};
+use super::designator::Designator;
+use super::diagnostic::Diagnostic;
+use super::group::Group;
+use super::parser::Parser;
+use super::range::{DesignatorIter, DesignatorRange};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::str::FromStr;
+
+// 範囲としてまとめられる参照名の分類
+// 接尾辞がない場合は番号、ある場合は接尾辞が連続する
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+struct Class {
+    group: Option<Group>,
+    // 階層参照名の場合は上位の階層
+    path: String,
+    prefix: String,
+    // ゼロ埋めの桁数 (R001 と R1 は別の参照名)
+    width: usize,
+    attribute: Option<String>,
+    // 接尾辞がある場合の番号と、接尾辞が小文字かどうか
+    suffix: Option<(usize, bool)>,
+}
+
+impl Class {
+    fn of(designator: &Designator) -> Self {
+        Self {
+            group: designator.group(),
+            path: designator
+                .path()
+                .iter()
+                .map(|segment| segment.to_string())
+                .chain(designator.sign().map(String::from))
+                .collect(),
+            prefix: designator.prefix().to_string(),
+            width: designator.padded_width(),
+            attribute: designator.attribute().map(String::from),
+            suffix: designator.suffix().map(|s| {
+                (
+                    designator.number(),
+                    s.chars().all(|c| c.is_ascii_lowercase()),
+                )
+            }),
+        }
+    }
+}
+
+// 分類ごとに保持できるように範囲を分ける
+// ゲートの範囲は番号ごとに (U1C~U2B -> U1C~U1D, U2A~U2B)、
+// ゼロ埋めされた範囲は桁数が足りてゼロ埋めが不要になる位置で (R098~R102 -> R098~R099, R100~R102)
+fn split_by_class(range: DesignatorRange) -> Vec<DesignatorRange> {
+    if let Some(gates) = range.gates() {
+        return split_by_number(&range, gates);
+    }
+    if Class::of(range.start()) == Class::of(range.end()) {
+        return vec![range];
+    }
+    let Some(last) = range.start().last_padded() else {
+        return vec![range];
+    };
+    let rest = last
+        .next()
+        .and_then(|first| DesignatorRange::new(first, range.end().clone()));
+    DesignatorRange::new(range.start().clone(), last)
+        .into_iter()
+        .chain(rest)
+        .collect()
+}
+
+fn split_by_number(range: &DesignatorRange, gates: usize) -> Vec<DesignatorRange> {
+    let (start, end) = (range.start(), range.end());
+    let (Some(first), Some(last)) = (start.suffix_index(), end.suffix_index()) else {
+        return Vec::new();
+    };
+    (start.number()..=end.number())
+        .filter_map(|number| {
+            let first = if number == start.number() { first } else { 1 };
+            let last = if number == end.number() { last } else { gates };
+            DesignatorRange::new(
+                start.with_gate(number, first),
+                start.with_gate(number, last),
+            )
+        })
+        .collect()
+}
+
+// 参照名の集合
+// 分類ごとに連続する参照名を範囲として保持する
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct DesignatorSet {
+    runs: BTreeMap<Class, Vec<DesignatorRange>>,
+}
+
+impl DesignatorSet {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn len(&self) -> usize {
+        self.ranges()
+            .fold(0usize, |acc, range| acc.saturating_add(range.count()))
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.runs.is_empty()
+    }
+
+    pub fn ranges(&self) -> impl Iterator<Item = &DesignatorRange> {
+        self.runs.values().flatten()
+    }
+
+    pub fn iter(&self) -> DesignatorIter {
+        DesignatorIter::new(self.ranges().cloned().collect())
+    }
+
+    pub fn contains(&self, designator: &Designator) -> bool {
+        self.runs.get(&Class::of(designator)).is_some_and(|runs| {
+            runs.iter().any(|run| {
+                designator
+                    .difference(run.start())
+                    .is_some_and(|diff| diff >= 0)
+                    && run
+                        .end()
+                        .difference(designator)
+                        .is_some_and(|diff| diff >= 0)
+            })
+        })
+    }
+
+    pub fn insert(&mut self, designator: Designator) -> bool {
+        if self.contains(&designator) {
+            return false;
+        }
+        self.insert_range(DesignatorRange::single(designator));
+        true
+    }
+
+    pub fn remove(&mut self, designator: &Designator) -> bool {
+        if !self.contains(designator) {
+            return false;
+        }
+        self.remove_range(&DesignatorRange::single(designator.clone()));
+        true
+    }
+
+    pub fn insert_range(&mut self, range: DesignatorRange) {
+        for range in split_by_class(range) {
+            self.insert_class_range(range);
+        }
+    }
+
+    pub fn remove_range(&mut self, range: &DesignatorRange) {
+        for range in split_by_class(range.clone()) {
+            self.remove_class_range(&range);
+        }
+    }
+
+    fn insert_class_range(&mut self, range: DesignatorRange) {
+        let runs = self.runs.entry(Class::of(range.start())).or_default();
+
+        // 重なる、もしくは隣接する範囲を1つにまとめる
+        let (merged, mut rest): (Vec<_>, Vec<_>) = runs.drain(..).partition(|run| {
+            run.end()
+                .difference(range.start())
+                .is_some_and(|diff| diff >= -1)
+                && range
+                    .end()
+                    .difference(run.start())
+                    .is_some_and(|diff| diff >= -1)
+        });
+        let start = merged
+            .iter()
+            .map(|run| run.start())
+            .chain([range.start()])
+            .min()
+            .unwrap()
+            .clone();
+        let end = merged
+            .iter()
+            .map(|run| run.end())
+            .chain([range.end()])
+            .max()
+            .unwrap()
+            .clone();
+        // 同じ分類内なので、範囲の生成は失敗しない
+        rest.push(DesignatorRange::new(start, end).unwrap());
+        rest.sort_by(|a, b| a.start().cmp(b.start()));
+
+        *runs = rest;
+    }
+
+    fn remove_class_range(&mut self, range: &DesignatorRange) {
+        let class = Class::of(range.start());
+        let Some(runs) = self.runs.get_mut(&class) else {
+            return;
+        };
+
+        let mut rest: Vec<DesignatorRange> = Vec::new();
+        for run in runs.drain(..) {
+            // 重ならない範囲はそのまま
+            let is_overlapped = run.end() >= range.start() && run.start() <= range.end();
+            if !is_overlapped {
+                rest.push(run);
+                continue;
+            }
+            // 前に残る範囲
+            if run.start() < range.start() {
+                rest.extend(
+                    range
+                        .start()
+                        .prev()
+                        .and_then(|end| DesignatorRange::new(run.start().clone(), end)),
+                );
+            }
+            // 後に残る範囲
+            if run.end() > range.end() {
+                rest.extend(
+                    range
+                        .end()
+                        .next()
+                        .and_then(|start| DesignatorRange::new(start, run.end().clone())),
+                );
+            }
+        }
+
+        if rest.is_empty() {
+            self.runs.remove(&class);
+        } else {
+            *runs = rest;
+        }
+    }
+
+    pub fn union(&self, other: &Self) -> Self {
+        let mut set = self.clone();
+        for range in other.ranges() {
+            set.insert_range(range.clone());
+        }
+        set
+    }
+
+    pub fn intersection(&self, other: &Self) -> Self {
+        let mut set = Self::new();
+        for (class, runs) in &self.runs {
+            let Some(other_runs) = other.runs.get(class) else {
+                continue;
+            };
+            for a in runs {
+                for b in other_runs {
+                    let start = a.start().max(b.start()).clone();
+                    let end = a.end().min(b.end()).clone();
+                    if let Some(range) = DesignatorRange::new(start, end) {
+                        set.insert_range(range);
+                    }
+                }
+            }
+        }
+        set
+    }
+
+    pub fn difference(&self, other: &Self) -> Self {
+        let mut set = self.clone();
+        for range in other.ranges() {
+            set.remove_range(range);
+        }
+        set
+    }
+
+    pub fn symmetric_difference(&self, other: &Self) -> Self {
+        self.difference(other).union(&other.difference(self))
+    }
+}
+
+impl fmt::Display for DesignatorSet {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        // 範囲は展開せずにそのまま出力する
+        f.write_str(&builder::build_ranges(
+            self.ranges().cloned().collect(),
+            &BuildOptions::default(),
+        ))
+    }
+}
+
+impl FromStr for DesignatorSet {
+    type Err = Diagnostic;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        // 範囲は展開せずにそのまま追加する
+        let mut set = Self::new();
+        for range in Parser::new(s).designators()?.into_ranges() {
+            set.insert_range(range);
+        }
+        Ok(set)
+    }
+}
+
+impl FromIterator<Designator> for DesignatorSet {
+    fn from_iter<T: IntoIterator<Item = Designator>>(iter: T) -> Self {
+        let mut set = Self::new();
+        set.extend(iter);
+        set
+    }
+}
+
+impl Extend<Designator> for DesignatorSet {
+    fn extend<T: IntoIterator<Item = Designator>>(&mut self, iter: T) {
+        for designator in iter {
+            self.insert(designator);
+        }
+    }
+}
@@ -1,5 +1,7 @@
+use crate::group::Group;
 use std::fmt;
 use std::fmt::Write;
+use std::ops::Range;
 
 pub(crate) const WHITESPACE: char = ' ';
 pub(crate) const COMMA: char = ',';
@@ -14,7 +16,7 @@ pub enum Token {
     Comma,
     CloseParen,
     OpenParen,
-    Range(char),
+    Range(String),
     Identifier(String),
 }
 
@@ -36,7 +38,7 @@ impl fmt::Display for Token {
             Token::Comma => f.write_char(COMMA)?,
             Token::CloseParen => f.write_char(CLOSE_PAREN)?,
             Token::OpenParen => f.write_char(OPEN_PAREN)?,
-            Token::Range(c) => f.write_char(*c)?,
+            Token::Range(op) => f.write_str(op)?,
             Token::Identifier(ident) => f.write_str(ident)?,
         }
 
@@ -44,10 +46,21 @@ impl fmt::Display for Token {
     }
 }
 
-#[derive(PartialEq, Debug)]
+#[derive(Debug)]
 pub struct TokenWithSymbol {
     symbol: char,
     token: Token,
+    // 元の入力文字列でのバイト位置
+    span: Range<usize>,
+    // 括弧の種類、括弧内の識別子の場合は囲む括弧の種類
+    group: Option<Group>,
+}
+
+// 位置は比較対象外とする
+impl PartialEq for TokenWithSymbol {
+    fn eq(&self, other: &Self) -> bool {
+        self.symbol == other.symbol && self.token == other.token && self.group == other.group
+    }
 }
 
 impl fmt::Display for TokenWithSymbol {
@@ -60,10 +73,37 @@ impl TokenWithSymbol {
     pub fn new(token: Token) -> Self {
         Self {
             symbol: get_token_symbol(&token),
+            group: match token {
+                Token::OpenParen | Token::CloseParen => Some(Group::Paren),
+                _ => None,
+            },
             token,
+            span: 0..0,
         }
     }
 
+    pub fn with_group(mut self, group: Group) -> Self {
+        self.group = Some(group);
+        self
+    }
+
+    pub fn group(&self) -> Option<Group> {
+        self.group
+    }
+
+    pub fn with_span(mut self, span: Range<usize>) -> Self {
+        self.span = span;
+        self
+    }
+
+    pub fn span(&self) -> Range<usize> {
+        self.span.clone()
+    }
+
+    pub fn extend_span(&mut self, span: Range<usize>) {
+        self.span = self.span.start.min(span.start)..self.span.end.max(span.end);
+    }
+
     pub fn symbol(&self) -> char {
         self.symbol
     }
@@ -112,11 +152,21 @@ impl TokenWithSymbol {
         // 大文字に変換されている場合も考慮して、自身が識別子でないときにのみ実行する
         if !self.is_identifier() {
             self.symbol = IDENTIFIER;
+            // 括弧は識別子の一部として種類に合わせた文字にする
+            let bracket = match (&self.token, self.group) {
+                (Token::OpenParen, Some(group)) => Some(group.open()),
+                (Token::CloseParen, Some(group)) => Some(group.close()),
+                _ => None,
+            };
+            if let Some(c) = bracket {
+                self.token = Token::Identifier(c.to_string());
+            }
         }
     }
 
-    pub fn parenthesize(&mut self) {
+    pub fn parenthesize(&mut self, group: Group) {
         self.symbol.make_ascii_uppercase();
+        self.group = Some(group);
     }
 
     pub fn transform(&mut self) {
@@ -126,15 +176,16 @@ impl TokenWithSymbol {
                 COMMA => self.token = Token::Comma,
                 CLOSE_PAREN => self.token = Token::CloseParen,
                 OPEN_PAREN => self.token = Token::OpenParen,
-                RANGE => self.token = Token::Range(RANGE),
+                RANGE => self.token = Token::Range(RANGE.to_string()),
                 IDENTIFIER => {
                     self.token = {
-                        let mut ident = self.token.to_string();
-                        if self.is_in_parentheses() {
-                            ident.insert(0, OPEN_PAREN);
-                            ident.push(CLOSE_PAREN);
+                        let ident = self.token.to_string();
+                        match self.group {
+                            Some(group) if self.is_in_parentheses() => {
+                                Token::Identifier(group.enclose(&ident))
+                            }
+                            _ => Token::Identifier(ident),
                         }
-                        Token::Identifier(ident)
                     }
                 }
                 _ => unreachable!(),